@@ -1,12 +1,16 @@
-use std::ffi::OsString;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
+use std::fs;
+use std::io;
 use std::path::Path;
 use std::str;
 
 use regex::bytes::Regex;
 
-use {Error, new_regex};
-use pathutil::file_name_ext;
+use {DEFAULT_SIZE_LIMIT, Error, ErrorKind, new_regex};
+use pathutil::{collapse_dots_and_slashes, file_name_ext};
 
 /// Describes a matching strategy for a particular pattern.
 ///
@@ -20,12 +24,22 @@ pub enum MatchStrategy {
     /// A pattern matches if and only if the entire file path is equal to
     /// the literal string given.
     Literal(String),
+    /// A pattern matches if and only if the entire file path is equal to
+    /// one of the literal strings given. This is what a top-level
+    /// alternation of pure literals, e.g. `{foo,bar,baz}.txt`, expands to.
+    Literals(Vec<String>),
     /// A pattern matches if and only if the file path's basename is equal
     /// to the literal string given.
     BasenameLiteral(String),
     /// A pattern matches if and only if the file path's extension matches
     /// the extension given.
     Extension(OsString),
+    /// A pattern matches if and only if the file path ends with the
+    /// (dot-included) compound extension given, e.g. `.tar.gz`. This is
+    /// the multi-dot generalization of `Extension`, for a pattern like
+    /// `*.tar.gz` whose suffix doesn't fit `file_name_ext`'s single
+    /// last-dot extension.
+    CompoundExtension(String),
     /// A pattern matches if and only if the file path starts with the
     /// prefix given.
     Prefix(String),
@@ -49,17 +63,23 @@ pub enum MatchStrategy {
 impl MatchStrategy {
     /// Returns a matching strategy for the given glob.
     pub fn new(glob: &Glob) -> MatchStrategy {
-        if let Some(lit) = glob.basename_literal() {
+        if glob.opts.force_regex || !glob.opts.anchored {
+            MatchStrategy::Regex
+        } else if let Some(lit) = glob.basename_literal() {
             MatchStrategy::BasenameLiteral(lit)
         } else if let Some(lit) = glob.literal() {
-            MatchStrategy::Literal(lit)
+            MatchStrategy::Literal(lit.to_string())
+        } else if let Some(lits) = glob.literal_alternates() {
+            MatchStrategy::Literals(lits)
         } else if let Some(ext) = glob.ext() {
             MatchStrategy::Extension(ext)
+        } else if let Some(suffix) = glob.compound_ext() {
+            MatchStrategy::CompoundExtension(suffix)
         } else if let Some(prefix) = glob.prefix() {
             MatchStrategy::Prefix(prefix)
         } else if let Some((suffix, component)) = glob.suffix() {
             MatchStrategy::Suffix { suffix: suffix, component: component }
-        } else if let Some(ext) = glob.required_ext() {
+        } else if let Some(ext) = glob.required_ext.clone() {
             MatchStrategy::RequiredExtension(ext)
         } else {
             MatchStrategy::Regex
@@ -67,6 +87,27 @@ impl MatchStrategy {
     }
 }
 
+/// Controls how `GlobBuilder::build` treats an empty pattern, e.g.
+/// `Glob::new("")`.
+///
+/// Set via `GlobBuilder::empty_matches`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum EmptyMode {
+    /// `build` returns `Err` with `ErrorKind::EmptyGlob`.
+    Error,
+    /// The compiled `Glob` never matches any path.
+    MatchNothing,
+    /// The compiled `Glob` matches only the empty path. This is this
+    /// crate's original, still-default behavior.
+    MatchEmpty,
+}
+
+impl Default for EmptyMode {
+    fn default() -> EmptyMode {
+        EmptyMode::MatchEmpty
+    }
+}
+
 /// Options to control the matching semantics of a glob.
 ///
 /// These are set via `GlobBuilder`.
@@ -78,6 +119,74 @@ struct GlobOptions {
     literal_separator: bool,
     /// Whether `\` can be used to escape special characters.
     backslash_escape: bool,
+    /// Whether ksh/bash-style extended glob operators are recognized.
+    extended_glob: bool,
+    /// Whether a leading `!` is treated as a negation flag rather than
+    /// part of the pattern.
+    negation: bool,
+    /// Whether `\` is treated as an additional path separator alongside
+    /// `/`, regardless of host platform.
+    cross_platform_separators: bool,
+    /// The character treated as the path separator, in place of `/`. `None`
+    /// means the ordinary `/` behavior, including `cross_platform_separators`.
+    separator: Option<char>,
+    /// The maximum number of path components a `**` is allowed to consume.
+    /// `None` means unbounded, the historical behavior.
+    max_globstar_depth: Option<usize>,
+    /// Whether to skip every specialized strategy and always compile to a
+    /// plain regex, regardless of the glob's shape.
+    force_regex: bool,
+    /// The size limit, in bytes, placed on this pattern's compiled regex
+    /// program.
+    regex_size_limit: usize,
+    /// The size limit, in bytes, placed on this pattern's lazy DFA cache.
+    dfa_size_limit: usize,
+    /// Whether a trailing `/` is recognized as gitignore-style "directory
+    /// only" marker rather than an ordinary path separator.
+    directory_matching: bool,
+    /// Whether `*`/`?` at the start of a path component can match a
+    /// leading `.`.
+    match_leading_dot: bool,
+    /// Whether a `**` that isn't bracketed by `/` (or the start/end of the
+    /// pattern) on every side it needs it is rejected outright, rather
+    /// than demoted to two ordinary `*` wildcards.
+    strict_globstar: bool,
+    /// Whether a trailing `/**` also matches the directory it's attached
+    /// to, in addition to everything beneath it.
+    globstar_matches_self: bool,
+    /// Whether `*`/`?` and `{...}` alternations are compiled into
+    /// capturing groups, so `GlobMatcher::captures` can report what each
+    /// one matched.
+    capture_groups: bool,
+    /// Whether the pattern must match the whole path, rather than merely
+    /// appear somewhere within it.
+    anchored: bool,
+    /// Whether the compiled matcher runs against just a candidate's
+    /// basename, rather than its whole path.
+    basename_only: bool,
+    /// Whether `?` matches exactly one byte, rather than one full Unicode
+    /// scalar value (which may be several bytes long).
+    question_matches_bytes: bool,
+    /// Whether `*` refuses to match across a `.`, the same way
+    /// `literal_separator` makes it refuse to match across the path
+    /// separator.
+    star_stops_at_dot: bool,
+    /// Whether the literal portions of the pattern are lowercased at
+    /// compile time, instead of matching case insensitively via the regex
+    /// `i` flag.
+    lowercase: bool,
+    /// Whether the compiled matcher runs against just a candidate's stem
+    /// (its basename with the extension removed), rather than its whole
+    /// path.
+    stem_only: bool,
+    /// How an empty pattern, e.g. `Glob::new("")`, is treated.
+    empty_matches: EmptyMode,
+    /// Whether leading and trailing whitespace is stripped from the
+    /// pattern before parsing.
+    trim: bool,
+    /// Whether an unescaped, out-of-class trailing `# comment` is stripped
+    /// from the pattern before parsing.
+    ignore_trailing_comment: bool,
 }
 
 impl GlobOptions {
@@ -86,6 +195,28 @@ impl GlobOptions {
             case_insensitive: false,
             literal_separator: false,
             backslash_escape: !cfg!(windows),
+            extended_glob: false,
+            negation: false,
+            cross_platform_separators: false,
+            separator: None,
+            max_globstar_depth: None,
+            force_regex: false,
+            regex_size_limit: DEFAULT_SIZE_LIMIT,
+            dfa_size_limit: DEFAULT_SIZE_LIMIT,
+            directory_matching: false,
+            match_leading_dot: true,
+            strict_globstar: true,
+            globstar_matches_self: true,
+            capture_groups: false,
+            anchored: true,
+            basename_only: false,
+            question_matches_bytes: true,
+            star_stops_at_dot: false,
+            lowercase: false,
+            stem_only: false,
+            empty_matches: EmptyMode::MatchEmpty,
+            trim: false,
+            ignore_trailing_comment: false,
         }
     }
 }
@@ -101,9 +232,36 @@ enum Token {
     RecursiveZeroOrMore,
     Class {
         negated: bool,
-        ranges: Vec<(char, char)>,
+        items: Vec<ClassItem>,
     },
     Alternates(Vec<Tokens>),
+    ExtGlob(ExtGlobKind, Vec<Tokens>),
+}
+
+/// A single item within a `[...]` character class.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+enum ClassItem {
+    /// An inclusive range of literal characters, e.g. `a-z`.
+    Range(char, char),
+    /// A POSIX named class, e.g. `[:alpha:]`.
+    Named(&'static str),
+}
+
+/// The repetition semantics of an extglob operator.
+///
+/// `!(...)` is deliberately absent: the regex engine used here has no
+/// lookaround, so there's no way to compile a complement pattern, and
+/// `GlobBuilder::extended_glob` rejects it with a clear error instead.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+enum ExtGlobKind {
+    /// `?(...)`: zero or one of the alternatives.
+    ZeroOrOne,
+    /// `*(...)`: zero or more of the alternatives.
+    ZeroOrMore,
+    /// `+(...)`: one or more of the alternatives.
+    OneOrMore,
+    /// `@(...)`: exactly one of the alternatives.
+    ExactlyOne,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
@@ -120,8 +278,21 @@ pub struct Glob {
     re: String,
     opts: GlobOptions,
     tokens: Tokens,
+    negated: bool,
+    literal: Option<String>,
+    literal_prefix: String,
+    required_ext: Option<OsString>,
+    dir_only: bool,
 }
 
+/// `Glob` equality is syntactic, not semantic: two globs are equal only if
+/// they were built from the same pattern string with the same options, so
+/// `Glob::new("./foo")` and `Glob::new("foo")` are *not* equal even though
+/// they compile to the same regex and match the same paths. Comparing the
+/// compiled regex instead would make equality (and thus hashing, for a
+/// `HashSet<Glob>`) blind to the difference between two patterns that
+/// happen to coincide today but could diverge under a future option this
+/// crate adds, so the pattern text itself is kept as the source of truth.
 impl PartialEq for Glob {
     fn eq(&self, other: &Glob) -> bool {
         self.glob == other.glob && self.opts == other.opts
@@ -146,33 +317,198 @@ impl fmt::Display for Glob {
 impl str::FromStr for Glob {
     type Err = Error;
 
+    /// Parses a glob with default options, identical to `Glob::new`.
+    ///
+    /// This lets a glob pattern be built with `s.parse::<Glob>()`, which is
+    /// convenient when collecting globs from an iterator of strings.
     fn from_str(glob: &str) -> Result<Glob, Error> {
         Glob::new(glob)
     }
 }
 
+impl<'a> ::std::convert::TryFrom<&'a str> for Glob {
+    type Error = Error;
+
+    /// Parses a glob with default options, identical to `Glob::new`.
+    ///
+    /// ```
+    /// # fn example() -> Result<(), globset::Error> {
+    /// use std::convert::TryFrom;
+    /// use globset::Glob;
+    ///
+    /// let glob = try!(Glob::try_from("*.rs")).compile_matcher();
+    /// assert!(glob.is_match("main.rs"));
+    /// # Ok(()) } example().unwrap();
+    /// ```
+    fn try_from(glob: &'a str) -> Result<Glob, Error> {
+        Glob::new(glob)
+    }
+}
+
+impl ::std::convert::TryFrom<String> for Glob {
+    type Error = Error;
+
+    /// Parses a glob with default options, identical to `Glob::new`.
+    ///
+    /// ```
+    /// # fn example() -> Result<(), globset::Error> {
+    /// use std::convert::TryFrom;
+    /// use globset::Glob;
+    ///
+    /// let glob = try!(Glob::try_from("*.rs".to_string())).compile_matcher();
+    /// assert!(glob.is_match("main.rs"));
+    /// # Ok(()) } example().unwrap();
+    /// ```
+    fn try_from(glob: String) -> Result<Glob, Error> {
+        Glob::new(&glob)
+    }
+}
+
 impl Glob {
     /// Builds a new pattern with default options.
     pub fn new(glob: &str) -> Result<Glob, Error> {
         GlobBuilder::new(glob).build()
     }
 
+    /// Builds a new pattern with default options from an `OsStr`.
+    ///
+    /// This is useful when a pattern comes from `std::env::args_os` or
+    /// elsewhere as an `OsString` that may not be valid UTF-8. Since this
+    /// crate's glob parser works on `&str`, the `OsStr` must itself be
+    /// valid UTF-8; if it isn't, `ErrorKind::InvalidUtf8` is returned.
+    pub fn from_os_str(glob: &OsStr) -> Result<Glob, Error> {
+        match glob.to_str() {
+            Some(glob) => Glob::new(glob),
+            None => Err(Error::from_kind(ErrorKind::InvalidUtf8)),
+        }
+    }
+
+    /// Builds a glob that matches any one of `members`, exactly, with each
+    /// member escaped so that any glob metacharacters it contains (`,`,
+    /// `{`, `}`, `*`, `?`, `[`, `\`) are matched literally rather than
+    /// interpreted.
+    ///
+    /// This is meant to replace hand-built `{a,b,c}` alternation patterns,
+    /// where joining members with `,` is only safe if none of them happen
+    /// to contain a `,`, `}`, or another metacharacter, which is easy to
+    /// get wrong when the members come from outside the program.
+    ///
+    /// ```
+    /// # fn example() -> Result<(), globset::Error> {
+    /// use globset::Glob;
+    ///
+    /// let glob = try!(Glob::alternation(&["a,b", "c}d"])).compile_matcher();
+    /// assert!(glob.is_match("a,b"));
+    /// assert!(glob.is_match("c}d"));
+    /// assert!(!glob.is_match("a"));
+    /// # Ok(()) } example().unwrap();
+    /// ```
+    pub fn alternation(members: &[&str]) -> Result<Glob, Error> {
+        let joined = members
+            .iter()
+            .map(|m| escape_alternation_member(m))
+            .collect::<Vec<_>>()
+            .join(",");
+        GlobBuilder::new(&format!("{{{}}}", joined))
+            .backslash_escape(true)
+            .build()
+    }
+
+    /// Builds a new pattern from `glob` after substituting every
+    /// `$VAR`/`${VAR}` placeholder it contains with the matching entry in
+    /// `vars`, e.g. `$HOME/*.conf` with `vars["HOME"] = "/home/bob"`
+    /// becomes `/home/bob/*.conf`.
+    ///
+    /// A substituted value is escaped the same way `Glob::alternation`
+    /// escapes its members, so a value containing a glob metacharacter
+    /// (e.g. a directory named `a[b]`) is matched literally rather than
+    /// interpreted. A placeholder whose name isn't in `vars`, including an
+    /// unterminated `${...}`, is an error: silently leaving it as literal
+    /// text, or dropping it, both risk building a pattern that matches
+    /// something other than what the caller's template meant. A bare `$`
+    /// not followed by a name (e.g. at the end of the pattern, or before a
+    /// character that can't start one) is left alone.
+    pub fn new_with_vars(
+        glob: &str,
+        vars: &HashMap<String, String>,
+    ) -> Result<Glob, Error> {
+        let expanded = try!(expand_vars(glob, vars));
+        GlobBuilder::new(&expanded).backslash_escape(true).build()
+    }
+
     /// Returns the original glob pattern used to build this pattern.
     pub fn glob(&self) -> &str {
         &self.glob
     }
 
     /// Returns the regular expression string for this glob.
+    ///
+    /// This is the same regex `compile_matcher` builds a `Regex` from
+    /// internally, exposed so that callers can reuse the translation, e.g.
+    /// to fold several globs into one `regex::bytes::RegexSet`, or just to
+    /// log or debug what a pattern compiled to.
     pub fn regex(&self) -> &str {
         &self.re
     }
 
+    /// Returns whether this pattern was built with a leading `!` negation
+    /// flag, via `GlobBuilder::negation`.
+    ///
+    /// When true, the `!` has already been stripped and does not appear in
+    /// `regex()` or in the compiled matcher; callers are expected to use
+    /// this to implement their own override logic (e.g. gitignore-style
+    /// un-ignoring) on top of an ordinary match.
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Returns whether this pattern ended with a `/` under
+    /// `GlobBuilder::directory_matching`, marking it as matching only
+    /// directories rather than any path.
+    pub fn is_dir_only(&self) -> bool {
+        self.dir_only
+    }
+
     /// Builds a new matcher from this pattern.
     pub fn compile_matcher(&self) -> GlobMatcher {
-        let re = new_regex(&self.re).expect("regex compilation shouldn't fail");
+        let re = new_regex(
+            &self.re, self.opts.regex_size_limit, self.opts.dfa_size_limit)
+            .expect("regex compilation shouldn't fail");
         GlobMatcher { pat: self.clone(), re: re }
     }
 
+    /// Builds a new matcher from this pattern, moving it in rather than
+    /// cloning it, for callers that don't need the `Glob` once it's
+    /// compiled.
+    pub fn into_matcher(self) -> GlobMatcher {
+        let re = new_regex(
+            &self.re, self.opts.regex_size_limit, self.opts.dfa_size_limit)
+            .expect("regex compilation shouldn't fail");
+        GlobMatcher { pat: self, re: re }
+    }
+
+    /// Builds a matcher for this pattern backed by the same fast-path
+    /// strategies (literal, basename, extension, prefix, suffix, ...) that
+    /// `GlobSet` picks between when it's compiling a whole batch of
+    /// patterns, rather than always running `compile_matcher`'s general
+    /// regex.
+    ///
+    /// This is implemented as a `GlobSet` of one pattern, so it carries a
+    /// `GlobSet`'s per-match dispatch overhead (choosing among several
+    /// empty strategies) in exchange for skipping the regex engine
+    /// entirely on patterns like `*.rs` that `MatchStrategy` classifies as
+    /// `Extension` or `Literal`. Whether that trade wins over
+    /// `compile_matcher` depends on the pattern and the match volume.
+    pub fn compile_fast_matcher(&self) -> GlobFastMatcher {
+        let mut builder = ::GlobSetBuilder::new();
+        builder.regex_size_limit(self.opts.regex_size_limit);
+        builder.dfa_size_limit(self.opts.dfa_size_limit);
+        builder.add(self.clone());
+        let set = builder.build()
+            .expect("an already-compiled glob should always rebuild");
+        GlobFastMatcher { set: set }
+    }
+
     fn has_any_metacharacters(&self) -> bool {
         self.tokens.0.iter().any(|t| match *t {
             Token::Literal(_) => false,
@@ -180,22 +516,162 @@ impl Glob {
         })
     }
 
-    fn literal(&self) -> Option<String> {
-        if self.opts.case_insensitive {
+    /// Returns true if this glob matches every possible path, e.g. `**` or,
+    /// with `literal_separator` off (the default), a bare `*`.
+    ///
+    /// A `GlobSet` containing a universal pattern can never actually narrow
+    /// anything down, so this is meant for tools to flag a stray `**`/`*`
+    /// that was probably a mistake, the same way an unreachable `match` arm
+    /// gets flagged.
+    ///
+    /// This only recognizes patterns built entirely out of wildcard tokens.
+    /// A pattern that happens to be semantically universal by some other
+    /// construction, e.g. a character class spanning every valid
+    /// character, returns false; detecting those in general isn't
+    /// worthwhile for what is fundamentally a best-effort lint.
+    pub fn is_universal(&self) -> bool {
+        if self.opts.max_globstar_depth.is_some() {
+            return false;
+        }
+        !self.tokens.0.is_empty() && self.tokens.0.iter().all(|t| match *t {
+            Token::RecursivePrefix
+            | Token::RecursiveSuffix
+            | Token::RecursiveZeroOrMore => true,
+            Token::Any | Token::ZeroOrMore => !self.opts.literal_separator,
+            _ => false,
+        })
+    }
+
+    /// Returns true if and only if this glob is just a literal path with no
+    /// wildcards at all, e.g. `foo/bar.txt` but not `foo/*.txt`.
+    pub fn is_literal(&self) -> bool {
+        self.literal().is_some()
+    }
+
+    /// Returns the literal path this glob represents, if `is_literal()`.
+    pub fn literal(&self) -> Option<&str> {
+        self.literal.as_ref().map(|s| s.as_str())
+    }
+
+    /// Returns the literal string this glob matches, for display purposes,
+    /// e.g. `*.rs` for the pattern `[*].rs` built by `escape("*.rs")`, or
+    /// `None` if `is_literal()` is false.
+    ///
+    /// This is a semantically-named wrapper around `literal()` (which
+    /// already reports the fully unescaped string), meant for callers
+    /// building user-facing text like "matches file `*`" who want a name
+    /// that says so, rather than a caller having to already know that
+    /// `literal()` does the unescaping.
+    pub fn display_literal(&self) -> Option<String> {
+        self.literal().map(|s| s.to_string())
+    }
+
+    /// Computes `literal()`'s value from this glob's tokens. `None` if the
+    /// glob is case insensitive, accepts `\` as a path separator, or
+    /// contains any metacharacter.
+    ///
+    /// A single-character class like `[*]`, used to escape a metacharacter
+    /// (see `escape`/`Glob::alternation`), counts as its one literal
+    /// character here, same as an ordinary `Token::Literal`; anything else
+    /// class-shaped (negated, a range spanning more than one character, a
+    /// POSIX named class, ...) actually depends on more than one character
+    /// and isn't literal.
+    fn compute_literal(&self) -> Option<String> {
+        if self.opts.case_insensitive || self.opts.cross_platform_separators
+            || self.opts.separator.is_some() {
             return None;
         }
         let mut lit = String::new();
         for t in &self.tokens.0 {
             match *t {
                 Token::Literal(c) => lit.push(c),
+                Token::Class { negated: false, ref items }
+                        if items.len() == 1 => {
+                    match items[0] {
+                        ClassItem::Range(a, b) if a == b => lit.push(a),
+                        _ => return None,
+                    }
+                }
                 _ => return None,
             }
         }
         Some(lit)
     }
 
+    /// If this glob's tokens are a (possibly empty) run of literal
+    /// characters, followed by exactly one top-level `{...}` alternation
+    /// whose every branch is itself made of nothing but literal characters,
+    /// followed by another (possibly empty) run of literal characters,
+    /// returns each branch's fully expanded literal string, e.g.
+    /// `["foo.txt", "bar.txt"]` for `{foo,bar}.txt`.
+    ///
+    /// This lets an enum-like pattern that's really just a handful of
+    /// literal names, rather than a single literal or a genuine wildcard,
+    /// still be routed through the fast literal-lookup strategy instead of
+    /// falling back to a full regex scan. Returns `None` for anything else,
+    /// including more than one alternation or a branch that itself contains
+    /// a wildcard.
+    fn literal_alternates(&self) -> Option<Vec<String>> {
+        if self.opts.case_insensitive || self.opts.cross_platform_separators
+            || self.opts.separator.is_some() {
+            return None;
+        }
+        let mut tokens = self.tokens.0.iter().peekable();
+        let mut prefix = String::new();
+        while let Some(&&Token::Literal(c)) = tokens.peek() {
+            prefix.push(c);
+            tokens.next();
+        }
+        let branches = match tokens.next() {
+            Some(&Token::Alternates(ref alts)) => alts,
+            _ => return None,
+        };
+        let mut suffix = String::new();
+        for t in tokens {
+            match *t {
+                Token::Literal(c) => suffix.push(c),
+                _ => return None,
+            }
+        }
+        let mut lits = Vec::with_capacity(branches.len());
+        for branch in branches {
+            let mut lit = prefix.clone();
+            for t in &branch.0 {
+                match *t {
+                    Token::Literal(c) => lit.push(c),
+                    _ => return None,
+                }
+            }
+            lit.push_str(&suffix);
+            lits.push(lit);
+        }
+        Some(lits)
+    }
+
+    /// Returns the leading run of literal characters in this glob, before
+    /// the first wildcard, e.g. `src/foo/` for `src/foo/*.rs`. Empty if the
+    /// glob starts with a wildcard, and the whole pattern if it's fully
+    /// literal. Useful for pruning a directory walk before it descends
+    /// into a subtree that can't possibly contain a match.
+    pub fn literal_prefix(&self) -> &str {
+        &self.literal_prefix
+    }
+
+    fn compute_literal_prefix(&self) -> String {
+        let mut lit = String::new();
+        for t in &self.tokens.0 {
+            match *t {
+                Token::Literal(c) => lit.push(c),
+                _ => break,
+            }
+        }
+        lit
+    }
+
     fn basename_literal(&self) -> Option<String> {
-        if self.opts.case_insensitive {
+        if self.opts.case_insensitive || self.opts.cross_platform_separators
+            || self.opts.separator.is_some()
+            || self.opts.max_globstar_depth.is_some() {
             return None;
         }
         let mut tokens = self.tokens.0.iter();
@@ -214,7 +690,9 @@ impl Glob {
     }
 
     fn ext(&self) -> Option<OsString> {
-        if self.opts.case_insensitive {
+        if self.opts.case_insensitive || self.opts.cross_platform_separators
+            || self.opts.separator.is_some()
+            || self.opts.max_globstar_depth.is_some() {
             return None;
         }
         let mut tokens = self.tokens.0.iter();
@@ -240,11 +718,121 @@ impl Glob {
         if lit.is_empty() { None } else { Some(OsString::from(lit)) }
     }
 
-    fn required_ext(&self) -> Option<OsString> {
-        None
+    /// If this glob is exactly `*` followed by a literal run containing two
+    /// or more `.`-separated segments, e.g. `*.tar.gz`, returns the full
+    /// suffix, dot included, e.g. `.tar.gz`. A single-segment suffix like
+    /// `*.rs` is left to `ext()`/`required_ext` instead, since
+    /// `pathutil::file_name_ext` (and so `Candidate::ext`) only ever holds
+    /// the text after the last `.`, which can't represent a compound
+    /// extension like `tar.gz` on its own.
+    fn compound_ext(&self) -> Option<String> {
+        if self.opts.case_insensitive || self.opts.cross_platform_separators
+            || self.opts.separator.is_some() {
+            return None;
+        }
+        let mut tokens = self.tokens.0.iter();
+        match tokens.next() {
+            Some(&Token::ZeroOrMore) => {}
+            _ => return None,
+        }
+        let mut suffix = String::new();
+        for t in tokens {
+            match *t {
+                Token::Literal(c) if c != '/' => suffix.push(c),
+                _ => return None,
+            }
+        }
+        if suffix.starts_with('.') && suffix.matches('.').count() >= 2 {
+            Some(suffix)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the extension every path this glob matches must have, if
+    /// one can be determined, e.g. `rs` for `**/*.rs` or `src/*.rs`, even
+    /// though neither pattern is a plain `Extension` strategy match on its
+    /// own (the first because of the leading `src/`, the second because
+    /// `ext()` only recognizes the narrower `**/*.ext` shape).
+    ///
+    /// This is a necessary, not sufficient, condition: a path with this
+    /// extension isn't guaranteed to match, but a path without it never
+    /// will, which is exactly what `MatchStrategy::RequiredExtension`
+    /// needs to cheaply reject most non-matching candidates before
+    /// falling back to the pattern's full regex.
+    fn compute_required_ext(&self) -> Option<OsString> {
+        if self.opts.case_insensitive || self.opts.cross_platform_separators
+            || self.opts.separator.is_some() {
+            return None;
+        }
+        let mut ext = String::new();
+        let mut saw_dot = false;
+        for t in self.tokens.0.iter().rev() {
+            match *t {
+                Token::Literal(c) if c != '/' && c != '.' => ext.insert(0, c),
+                Token::Literal('.') => {
+                    saw_dot = true;
+                    break;
+                }
+                _ => break,
+            }
+        }
+        if saw_dot && !ext.is_empty() {
+            Some(OsString::from(ext))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the extension every path this glob matches must have. See
+    /// `compute_required_ext` for what "must have" means here.
+    pub fn required_extension(&self) -> Option<&OsStr> {
+        self.required_ext.as_ref().map(|s| s.as_os_str())
+    }
+
+    /// Returns true if some path could match both `self` and `other`.
+    ///
+    /// Precisely deciding this in general means answering whether two
+    /// regexes share a match, which this crate has no interest in
+    /// implementing. Instead this checks a handful of necessary conditions
+    /// for a shared match to exist — required extensions agreeing, and
+    /// literal prefixes not diverging — and returns `false` as soon as one
+    /// fails. If every check passes, this conservatively returns `true`,
+    /// even for some pairs that don't actually share a match. The one
+    /// exception is a glob with an exact `literal()`: there, the answer is
+    /// exact, since it only takes checking whether `other` matches that one
+    /// string.
+    ///
+    /// This is meant for linting a config file's glob list for redundant or
+    /// conflicting rules, where false positives just mean an occasional
+    /// unnecessary warning, but a false negative would hide a real overlap.
+    pub fn intersects(&self, other: &Glob) -> bool {
+        if let (Some(a), Some(b)) =
+            (self.required_extension(), other.required_extension())
+        {
+            if a != b {
+                return false;
+            }
+        }
+        if let Some(lit) = self.literal() {
+            return other.compile_matcher().is_match(lit);
+        }
+        if let Some(lit) = other.literal() {
+            return self.compile_matcher().is_match(lit);
+        }
+        let a = self.literal_prefix();
+        let b = other.literal_prefix();
+        if !a.starts_with(b) && !b.starts_with(a) {
+            return false;
+        }
+        true
     }
 
     fn prefix(&self) -> Option<String> {
+        if self.opts.cross_platform_separators || self.opts.separator.is_some()
+            || self.opts.max_globstar_depth.is_some() {
+            return None;
+        }
         if self.opts.case_insensitive || self.ends_with_recursive_suffix() {
             return self.prefix_impl();
         }
@@ -284,17 +872,231 @@ pub struct GlobMatcher {
 }
 
 impl GlobMatcher {
+    /// Returns the slice of `path` this matcher's regex should actually run
+    /// against: all of it, unless the `Glob` was built with
+    /// `GlobBuilder::basename_only(true)` or `GlobBuilder::stem_only(true)`,
+    /// in which case just the basename or stem, respectively.
+    fn select_bytes<'p>(&self, path: &'p [u8]) -> &'p [u8] {
+        if self.pat.opts.stem_only {
+            ::pathutil::file_stem_bytes(::pathutil::file_name_bytes(path))
+        } else if self.pat.opts.basename_only {
+            ::pathutil::file_name_bytes(path)
+        } else {
+            path
+        }
+    }
+
     /// Tests whether the given path matches this pattern.
     pub fn is_match<P: AsRef<Path>>(&self, path: P) -> bool {
         use pathutil::{normalize_path, path_bytes};
         let path = normalize_path(path_bytes(path.as_ref()));
-        self.re.is_match(&*path)
+        self.re.is_match(self.select_bytes(&path))
+    }
+
+    /// Tests whether the given candidate matches this pattern.
+    ///
+    /// This takes a `Candidate` as input, which can be used to amortize the
+    /// cost of preparing a path for matching across several `GlobMatcher`s,
+    /// the same way `GlobSet::is_match_candidate` does for a whole set.
+    pub fn is_match_candidate(&self, candidate: &::Candidate) -> bool {
+        if self.pat.opts.stem_only {
+            self.re.is_match(&*candidate.stem)
+        } else if self.pat.opts.basename_only {
+            self.re.is_match(&*candidate.basename)
+        } else {
+            self.re.is_match(&*candidate.path)
+        }
+    }
+
+    /// Tests whether the given `OsStr` matches this pattern.
+    ///
+    /// This matches directly on the OS string's bytes, without going
+    /// through `Path`, for callers that already have an `OsStr` in hand
+    /// (e.g. from `DirEntry::file_name`).
+    pub fn is_match_os(&self, path: &OsStr) -> bool {
+        use pathutil::os_str_bytes;
+        self.is_match_bytes(&os_str_bytes(path))
+    }
+
+    /// Tests whether the given raw path bytes match this pattern.
+    ///
+    /// This matches directly on `path`, without going through `Path` or
+    /// `OsStr`, so `path` need not be valid UTF-8 or a valid `OsStr`
+    /// encoding for the current platform.
+    pub fn is_match_bytes(&self, path: &[u8]) -> bool {
+        use pathutil::normalize_path;
+        let path = normalize_path(Cow::Borrowed(path));
+        self.re.is_match(self.select_bytes(&path))
+    }
+
+    /// Tests whether the given path matches this pattern, honoring
+    /// `Glob::is_dir_only`: if the pattern is directory-only, a path for
+    /// which `is_dir` is `false` never matches, regardless of `path`.
+    pub fn is_match_dir<P: AsRef<Path>>(&self, path: P, is_dir: bool) -> bool {
+        if self.pat.is_dir_only() && !is_dir {
+            return false;
+        }
+        self.is_match(path)
+    }
+
+    /// Tests whether the given directory entry matches this pattern,
+    /// honoring `Glob::is_dir_only` via the entry's `file_type`.
+    ///
+    /// This is a convenience for callers walking a directory with
+    /// `std::fs::read_dir`, sparing them from calling `path()` and
+    /// `file_type()` themselves and threading the result through
+    /// `is_match_dir`. Any error `DirEntry::file_type` returns (e.g. from
+    /// the extra `stat` it falls back to on platforms whose directory
+    /// entries don't already carry the file type) is propagated as-is.
+    pub fn is_match_entry(&self, entry: &fs::DirEntry) -> io::Result<bool> {
+        let is_dir = try!(entry.file_type()).is_dir();
+        Ok(self.is_match_dir(entry.path(), is_dir))
+    }
+
+    /// Tests whether `partial_path` could be the start of some longer path
+    /// this pattern matches, e.g. `src/l` is a prefix match for a pattern
+    /// that matches `src/lib.rs`.
+    ///
+    /// This is meant for live filtering in a picker or autocomplete list,
+    /// where the user hasn't finished typing yet. It's evaluated directly
+    /// against this pattern's tokens rather than its compiled regex, since
+    /// the `regex` crate has no way to ask "is this a prefix of some
+    /// accepted string" for an arbitrary compiled pattern.
+    ///
+    /// This is conservative in two ways: it doesn't check character class
+    /// membership (`[abc]`/`[[:alpha:]]`) or POSIX-class rules, treating
+    /// any single character as satisfying one, and it doesn't track
+    /// extglob repetition counts beyond "zero occurrences" vs "at least
+    /// one", so it can return `true` for a `partial_path` that, once
+    /// completed, wouldn't actually satisfy those parts of the pattern. It
+    /// never returns `false` for a `partial_path` that a real completion
+    /// would match. It also doesn't account for `basename_only` or
+    /// `stem_only`, since those slice the whole candidate before matching
+    /// in a way that doesn't have a meaningful "partial" analog.
+    pub fn is_prefix_match<P: AsRef<Path>>(&self, partial_path: P) -> bool {
+        use pathutil::{normalize_path, path_bytes};
+        let path = normalize_path(path_bytes(partial_path.as_ref()));
+        match str::from_utf8(&path) {
+            Ok(s) => {
+                let chars: Vec<char> = s.chars().collect();
+                tokens_could_prefix_match(
+                    &self.pat.tokens.0, &chars, &self.pat.opts)
+            }
+            Err(_) => false,
+        }
     }
 
     /// Returns the `Glob` used to construct this matcher.
     pub fn glob(&self) -> &Glob {
         &self.pat
     }
+
+    /// Filters `paths` down to just the ones this pattern matches,
+    /// preserving their original order.
+    ///
+    /// This is the mirror image of `GlobSet::matches`: one glob against
+    /// many paths, instead of one path against many globs. It's built on
+    /// `is_match_candidate`, so each path only pays for building a
+    /// `Candidate` once, the same amortization `GlobSet` gets from reusing
+    /// one across several matchers.
+    pub fn matches_paths<'a, P: AsRef<Path>>(&self, paths: &'a [P]) -> Vec<&'a P> {
+        paths.iter()
+            .filter(|p| self.is_match_candidate(&::Candidate::new(p.as_ref())))
+            .collect()
+    }
+
+    /// Returns the byte range of `path` that this pattern matched, or
+    /// `None` if it didn't match at all.
+    ///
+    /// Since every glob compiled by this crate is fully anchored, a
+    /// successful match always spans the whole of `path`; this is mostly
+    /// useful once a glob supports unanchored matching, at which point the
+    /// range narrows to just the matched substring.
+    pub fn find<P: AsRef<Path>>(&self, path: P) -> Option<(usize, usize)> {
+        use pathutil::{normalize_path, path_bytes};
+        let path = normalize_path(path_bytes(path.as_ref()));
+        self.re.find(self.select_bytes(&path))
+    }
+
+    /// Returns what each `*`/`?`/`{...}` in this pattern matched against
+    /// `path`, in the order they appear in the pattern, or `None` if
+    /// `path` doesn't match at all.
+    ///
+    /// This only reports anything useful if the `Glob` was built with
+    /// `GlobBuilder::capture_groups(true)`; otherwise the pattern has no
+    /// capturing groups to report on and this always returns `Some(vec![])`
+    /// for a matching path.
+    pub fn captures<P: AsRef<Path>>(&self, path: P) -> Option<Vec<String>> {
+        use pathutil::{normalize_path, path_bytes};
+        let path = normalize_path(path_bytes(path.as_ref()));
+        self.re.captures(self.select_bytes(&path)).map(|caps| {
+            caps.iter()
+                .skip(1)
+                .map(|group| match group {
+                    Some(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                    None => String::new(),
+                })
+                .collect()
+        })
+    }
+}
+
+/// A matcher for a single glob pattern, backed by `GlobSet`'s fast-path
+/// strategies rather than always running a general regex.
+///
+/// Built via `Glob::compile_fast_matcher`.
+#[derive(Clone, Debug)]
+pub struct GlobFastMatcher {
+    set: ::GlobSet,
+}
+
+impl GlobFastMatcher {
+    /// Tests whether the given path matches this pattern.
+    pub fn is_match<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.set.is_match(path)
+    }
+
+    /// Tests whether the given candidate matches this pattern.
+    ///
+    /// This takes a `Candidate` as input, which can be used to amortize the
+    /// cost of preparing a path for matching across several matchers.
+    pub fn is_match_candidate(&self, candidate: &::Candidate) -> bool {
+        self.set.is_match_candidate(candidate)
+    }
+}
+
+/// A cache of previously built `Glob`s, keyed by pattern text and options,
+/// for `GlobBuilder::build_cached`.
+///
+/// This exists for tools that rebuild mostly the same set of patterns over
+/// and over, e.g. an editor re-validating a config file's glob list on
+/// every keystroke, where only one or two patterns actually change between
+/// rebuilds. Entries are never evicted, so a cache reused across many
+/// distinct patterns over a long-running process's lifetime will grow
+/// without bound; callers that can't bound the set of patterns they'll
+/// ever see should create a fresh `GlobCache` periodically instead of
+/// reusing one forever.
+#[derive(Clone, Debug, Default)]
+pub struct GlobCache {
+    cached: HashMap<(String, GlobOptions), Glob>,
+}
+
+impl GlobCache {
+    /// Create a new, empty cache.
+    pub fn new() -> GlobCache {
+        GlobCache { cached: HashMap::new() }
+    }
+
+    /// Returns the number of distinct `(pattern, options)` pairs currently
+    /// cached.
+    pub fn len(&self) -> usize {
+        self.cached.len()
+    }
+
+    /// Returns true if and only if this cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.cached.is_empty()
+    }
 }
 
 /// A builder for a pattern.
@@ -322,17 +1124,144 @@ impl<'a> GlobBuilder<'a> {
 
     /// Parses and builds the pattern.
     pub fn build(&self) -> Result<Glob, Error> {
-        let tokens = try!(Parser::new(self.glob, self.opts.backslash_escape).parse());
+        let negated = self.opts.negation && self.glob.starts_with('!');
+        let raw = if negated { &self.glob[1..] } else { self.glob };
+        let raw = if self.opts.ignore_trailing_comment {
+            strip_trailing_comment(raw, self.opts.backslash_escape)
+        } else {
+            raw
+        };
+        let raw = if self.opts.trim {
+            trim_pattern_text(raw, self.opts.backslash_escape)
+        } else {
+            raw
+        };
+        // Collapse `./` components and repeated `/` in the pattern text
+        // the same way `pathutil::normalize_path` does for candidates, so
+        // a pattern like `src//foo/./*.rs` and a candidate normalized the
+        // same way agree on what they mean. `self.glob` itself (used below
+        // for error messages, and stored on `Glob` for `Display`/equality)
+        // is left untouched.
+        let normalized = collapse_pattern(raw);
+        let dir_only = self.opts.directory_matching
+            && normalized.ends_with('/');
+        let pattern = if dir_only {
+            &normalized[..normalized.len() - 1]
+        } else {
+            &normalized[..]
+        };
+        if pattern.is_empty() {
+            match self.opts.empty_matches {
+                EmptyMode::Error => {
+                    return Err(
+                        Error::from_kind(ErrorKind::EmptyGlob)
+                            .with_glob(self.glob));
+                }
+                EmptyMode::MatchEmpty => {}
+                EmptyMode::MatchNothing => {
+                    return self.build_impossible(negated, dir_only);
+                }
+            }
+        }
+        let sep = self.opts.separator.unwrap_or('/');
+        let mut tokens = try!(
+            Parser::new(
+                pattern, self.opts.backslash_escape, self.opts.extended_glob,
+                sep)
+                .parse()
+                .map_err(|err| err.with_glob(self.glob)));
+        try!(
+            validate_globstar_placement(
+                &mut tokens.0, self.opts.strict_globstar, sep)
+                .map_err(|err| err.with_glob(self.glob)));
+        if self.opts.lowercase {
+            lowercase_literals(&mut tokens.0);
+        }
         let re = tokens.to_regex(&self.opts);
         // Make sure the regex actually compiles, otherwise the error
         // reported to the caller would just be an internal panic.
-        try!(new_regex(&re));
-        Ok(Glob {
+        try!(
+            new_regex(&re, self.opts.regex_size_limit, self.opts.dfa_size_limit)
+                .map_err(|err| err.with_glob(self.glob))
+        );
+        let mut glob = Glob {
             glob: self.glob.to_string(),
             re: re,
             opts: self.opts,
             tokens: tokens,
-        })
+            negated: negated,
+            literal: None,
+            literal_prefix: String::new(),
+            required_ext: None,
+            dir_only: dir_only,
+        };
+        glob.literal = glob.compute_literal();
+        glob.literal_prefix = glob.compute_literal_prefix();
+        glob.required_ext = glob.compute_required_ext();
+        Ok(glob)
+    }
+
+    /// Builds a `Glob` that never matches any path, for
+    /// `EmptyMode::MatchNothing`.
+    ///
+    /// This compiles to `[^\x00-\xff]` under this crate's `(?-u)` byte mode:
+    /// a negated class spanning the entire byte value range can never match
+    /// any byte, so no candidate of any length ever satisfies it. The
+    /// tokens are a single `Class`, rather than left empty, so
+    /// `MatchStrategy::new` doesn't mistake this glob for a literal empty
+    /// string and route it to a strategy that actually matches the empty
+    /// path.
+    fn build_impossible(
+        &self,
+        negated: bool,
+        dir_only: bool,
+    ) -> Result<Glob, Error> {
+        let tokens = Tokens(vec![
+            Token::Class {
+                negated: true,
+                items: vec![ClassItem::Range('\u{0}', '\u{ff}')],
+            },
+        ]);
+        let re = tokens.to_regex(&self.opts);
+        try!(
+            new_regex(&re, self.opts.regex_size_limit, self.opts.dfa_size_limit)
+                .map_err(|err| err.with_glob(self.glob))
+        );
+        let mut glob = Glob {
+            glob: self.glob.to_string(),
+            re: re,
+            opts: self.opts,
+            tokens: tokens,
+            negated: negated,
+            literal: None,
+            literal_prefix: String::new(),
+            required_ext: None,
+            dir_only: dir_only,
+        };
+        glob.literal = glob.compute_literal();
+        glob.literal_prefix = glob.compute_literal_prefix();
+        glob.required_ext = glob.compute_required_ext();
+        Ok(glob)
+    }
+
+    /// Builds the pattern the same way `build` does, but consults `cache`
+    /// first: if this exact pattern text was already built with these exact
+    /// options, the cached `Glob` is cloned back out instead of re-parsing
+    /// the pattern and recompiling a regex for it. On a cache miss, the
+    /// freshly built `Glob` is stored in `cache` for next time.
+    ///
+    /// This is meant for interactive tools (e.g. a config editor
+    /// validating a glob set on every keystroke) that rebuild mostly the
+    /// same patterns over and over, with only one or two actually
+    /// changing between rebuilds.
+    pub fn build_cached(&self, cache: &mut GlobCache) -> Result<Glob, Error> {
+        let key = (self.glob.to_string(), self.opts);
+        if let Some(glob) = cache.cached.get(&key) {
+            return Ok(glob.clone());
+        }
+        let glob = try!(self.build());
+        cache.cached.insert(key, glob.clone());
+        Ok(glob)
     }
 
     /// Toggle whether the pattern matches case insensitively or not.
@@ -341,75 +1270,698 @@ impl<'a> GlobBuilder<'a> {
         self
     }
 
+    /// Toggle whether the pattern's literal portions are lowercased once at
+    /// compile time, rather than matched case insensitively via the regex
+    /// `i` flag.
+    ///
+    /// This is faster than `case_insensitive` for sets made up mostly of
+    /// literal patterns, since case is folded once here instead of on every
+    /// match. The tradeoff is on the caller: a `Glob` built with this set
+    /// only matches correctly against candidates whose path is *also*
+    /// already lowercased, e.g. one built from a string run through
+    /// `str::to_lowercase` before being handed to `Candidate::new`. This
+    /// crate does not lowercase candidates for you.
+    pub fn lowercase(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.lowercase = yes;
+        self
+    }
+
     /// Toggle whether a literal `/` is required to match a path separator.
     pub fn literal_separator(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
         self.opts.literal_separator = yes;
         self
     }
 
-    /// Toggle whether a `\` can be used to escape special characters.
+    /// Toggle whether a `\` can be used to escape special characters in a
+    /// pattern, e.g. `\*.rs` matches a file literally named `*.rs`.
+    ///
+    /// Defaults to `true` on Unix and `false` on Windows, since on Windows
+    /// `\` is also accepted as a path separator (see
+    /// `cross_platform_separators`); the two are mutually exclusive; if a
+    /// pattern needs both a literal `\` escape and `\` as a separator on
+    /// the same platform, escape it inside a class instead, e.g. `[*]`.
     pub fn backslash_escape(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
         self.opts.backslash_escape = yes;
         self
     }
-}
 
-struct Parser<'a> {
-    chars: ::std::iter::Peekable<str::Chars<'a>>,
-    backslash_escape: bool,
-}
+    /// Toggle whether leading and trailing whitespace is stripped from the
+    /// pattern before parsing.
+    ///
+    /// A trailing space that's escaped with `\` (gitignore's own convention
+    /// for a pattern that should match a path literally ending in a space)
+    /// is left alone even when this is on: trimming stops as soon as it
+    /// reaches a whitespace character preceded by an odd number of `\`s,
+    /// rather than continuing to strip through it. Only ASCII whitespace at
+    /// the very start and end is affected; whitespace in the middle of the
+    /// pattern is never touched. This is meant for patterns read from a
+    /// human-edited file, one per line, where trailing spaces are usually
+    /// just a typo rather than intentional. Defaults to `false`.
+    pub fn trim(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.trim = yes;
+        self
+    }
 
-impl<'a> Parser<'a> {
-    fn new(glob: &'a str, backslash_escape: bool) -> Parser<'a> {
-        Parser { chars: glob.chars().peekable(), backslash_escape: backslash_escape }
+    /// Toggle whether a trailing `# comment` is stripped from the pattern
+    /// before parsing.
+    ///
+    /// The `#` is only treated as a comment marker when it isn't escaped
+    /// with `\` and doesn't fall inside a `[...]` character class, so
+    /// `[#]` and `\#` both still match a literal `#`. This is meant for
+    /// patterns read from a human-edited file, alongside `trim`, which
+    /// should generally also be turned on to clean up the whitespace a
+    /// stripped comment leaves behind. Defaults to `false`.
+    pub fn ignore_trailing_comment(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.ignore_trailing_comment = yes;
+        self
     }
 
-    fn parse(mut self) -> Result<Tokens, Error> {
-        let mut tokens = vec![];
-        while let Some(c) = self.chars.next() {
-            match c {
-                '?' => tokens.push(Token::Any),
-                '*' => {
-                    if self.chars.peek() == Some(&'*') {
-                        self.chars.next();
-                        tokens.push(Token::RecursiveZeroOrMore);
-                    } else {
-                        tokens.push(Token::ZeroOrMore);
-                    }
-                }
-                '[' => tokens.push(try!(self.parse_class())),
-                '{' => return Err(Error::UnclosedAlternates),
-                '}' => return Err(Error::UnopenedAlternates),
-                '\\' if self.backslash_escape => {
-                    match self.chars.next() {
-                        Some(c) => tokens.push(Token::Literal(c)),
-                        None => tokens.push(Token::Literal('\\')),
-                    }
-                }
-                c => tokens.push(Token::Literal(c)),
-            }
+    /// Toggle whether ksh/bash-style extended glob operators are
+    /// recognized: `?(p1|p2)`, `*(p1|p2)`, `+(p1|p2)` and `@(p1|p2)`.
+    /// `!(p1|p2)` is rejected with an error, since this crate's regex
+    /// engine has no lookaround to compile a complement pattern.
+    pub fn extended_glob(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.extended_glob = yes;
+        self
+    }
+
+    /// Toggle whether a leading `!` is treated as a negation flag.
+    ///
+    /// When enabled, a pattern beginning with `!` has that `!` stripped
+    /// before parsing, `Glob::is_negated()` reports `true`, and the
+    /// compiled matcher behaves exactly as if the `!` weren't there. This
+    /// is off by default: an ordinary `Glob` treats a leading `!` as a
+    /// literal character, matching gitignore's own convention where `!`
+    /// only means negation when a caller (such as `parse_patterns`)
+    /// chooses to interpret it that way.
+    ///
+    /// To match a path that should literally start with `!` while this
+    /// option is enabled, escape it instead, e.g. `\!foo` (see
+    /// `GlobBuilder::backslash_escape`).
+    pub fn negation(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.negation = yes;
+        self
+    }
+
+    /// Toggle whether `\` is accepted as a path separator alongside `/`,
+    /// regardless of the host platform.
+    ///
+    /// This is useful when matching paths collected on Windows (which may
+    /// contain `\`) against a matcher compiled and run on a Unix host. When
+    /// enabled, every `/` in the pattern (whether written literally or
+    /// implied by `**`) matches either `/` or `\` in the candidate, and
+    /// `literal_separator`'s wildcard-stops-at-a-separator behavior treats
+    /// both characters as separators too. Enabling this forces the pattern
+    /// through the regex-based match strategy, since the faster
+    /// literal/prefix/suffix/extension strategies assume `/` is the only
+    /// separator. Off by default.
+    pub fn cross_platform_separators(
+        &mut self,
+        yes: bool,
+    ) -> &mut GlobBuilder<'a> {
+        self.opts.cross_platform_separators = yes;
+        self
+    }
+
+    /// Sets the character treated as the path separator, in place of `/`.
+    ///
+    /// This lets globset match against "paths" that aren't filesystem paths
+    /// at all, e.g. dotted identifiers like `a.b.c`, by making `separator('.')`
+    /// give `.` the same role `/` plays by default: `*`/`?` stop at it when
+    /// `literal_separator` is set, `**` consumes zero or more components
+    /// delimited by it, and a literal occurrence of it in the pattern is
+    /// what `Token::starts_next_component` treats as beginning a new one.
+    ///
+    /// A non-default separator is incompatible with `cross_platform_separators`
+    /// (which only ever means "`/` or `\`") and with the literal, basename,
+    /// extension, and prefix/suffix fast paths, which all assume `/` is the
+    /// only separator; setting one forces the pattern through
+    /// `MatchStrategy::Regex`, the same way `cross_platform_separators` does.
+    pub fn separator(&mut self, sep: char) -> &mut GlobBuilder<'a> {
+        self.opts.separator = Some(sep);
+        self
+    }
+
+    /// Bounds how many path components a `**` is allowed to consume, e.g.
+    /// with a depth of `2`, `foo/**/bar` matches `foo/a/b/bar` but not
+    /// `foo/a/b/c/bar`. `None` (the default) leaves `**` unbounded.
+    ///
+    /// This guards against pathological matching time or memory use when a
+    /// pattern is compiled from untrusted input and run against untrusted,
+    /// arbitrarily deep paths; it doesn't change what `**` means for any
+    /// path shallow enough to fit within the bound.
+    ///
+    /// Setting a depth forces the pattern through `MatchStrategy::Regex`,
+    /// since the literal, basename, extension, and prefix fast paths all
+    /// assume a leading or trailing `**` matches without limit.
+    pub fn max_globstar_depth(
+        &mut self,
+        depth: Option<usize>,
+    ) -> &mut GlobBuilder<'a> {
+        self.opts.max_globstar_depth = depth;
+        self
+    }
+
+    /// Force this pattern to compile via the plain regex strategy, skipping
+    /// every specialized strategy (literal, extension, prefix/suffix, etc.)
+    /// that `MatchStrategy::new` would otherwise pick.
+    ///
+    /// This exists to benchmark the specialized strategies against the
+    /// `RegexSet` fallback they exist to avoid; it doesn't change what a
+    /// pattern matches, only how a `GlobSet` recognizes it internally. Off
+    /// by default.
+    pub fn force_regex(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.force_regex = yes;
+        self
+    }
+
+    /// Toggle whether `*`/`?` at the start of a path component can match a
+    /// leading `.`. Defaults to `true`, preserving this crate's original
+    /// behavior; set to `false` for shell-style globbing, where `*.txt`
+    /// does not match `.hidden.txt` unless the pattern itself starts with
+    /// a `.`.
+    ///
+    /// "Start of a path component" means the very start of the pattern or
+    /// immediately after a literal `/`; this does not attempt to reason
+    /// about what a `**` or alternation might expand to.
+    pub fn match_leading_dot(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.match_leading_dot = yes;
+        self
+    }
+
+    /// Toggle whether a trailing `/` marks the pattern as matching only
+    /// directories, gitignore-style, e.g. `build/` matches a directory
+    /// named `build` but not a file by that name. The trailing `/` is
+    /// stripped before the pattern is parsed. Defaults to `false`, which
+    /// preserves the old behavior of treating a trailing `/` as an
+    /// ordinary path separator.
+    ///
+    /// When enabled, use `Glob::is_dir_only` to check whether a built
+    /// pattern carries this marker, and `GlobMatcher::is_match_dir` to
+    /// match while respecting it.
+    pub fn directory_matching(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.directory_matching = yes;
+        self
+    }
+
+    /// Toggle whether a `**` that isn't bracketed by `/` (or the start/end
+    /// of the pattern) on every side it needs it, e.g. the `**` in `a**b`
+    /// or `a/**b`, is rejected with `ErrorKind::InvalidRecursive`.
+    ///
+    /// Defaults to `true`. Set to `false` to instead demote a misplaced
+    /// `**` to two ordinary `*` wildcards, for callers that ingest
+    /// user-supplied patterns where `**` is used sloppily.
+    pub fn strict_globstar(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.strict_globstar = yes;
+        self
+    }
+
+    /// Toggle whether a trailing `/**`, e.g. in `foo/**`, also matches the
+    /// directory `foo` itself, in addition to everything beneath it.
+    /// Defaults to `true`. Set to `false` if `foo/**` should only match
+    /// paths that actually descend into `foo`.
+    ///
+    /// This has no effect on a leading `**/`, e.g. in `**/foo`, which
+    /// always matches zero or more leading directories and so already
+    /// matches the bare `foo`.
+    ///
+    /// This is the knob for tools like ripgrep's ignore handling that want
+    /// `foo/**` to also exclude `foo` itself.
+    pub fn globstar_matches_self(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.globstar_matches_self = yes;
+        self
+    }
+
+    /// Toggle whether `*`/`?` and `{...}` alternations are compiled into
+    /// capturing groups rather than the usual non-capturing `(?:...)`
+    /// groups, so that `GlobMatcher::captures` can report what each one
+    /// matched against a given path. Defaults to `false`.
+    ///
+    /// Capturing groups make the regex engine record each sub-match's byte
+    /// range as part of every search, which is slower than the plain
+    /// non-capturing groups this crate compiles by default; leave this
+    /// off unless you actually intend to call `GlobMatcher::captures`.
+    pub fn capture_groups(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.capture_groups = yes;
+        self
+    }
+
+    /// Toggle whether the pattern must match the whole path (the default)
+    /// or merely appear somewhere within it, e.g. so `node_modules` can
+    /// match `a/node_modules/b`.
+    ///
+    /// Defaults to `true`. Setting this to `false` drops the compiled
+    /// regex's leading `^` and trailing `$`, and, since the literal,
+    /// extension, prefix, and suffix fast-path strategies all assume a
+    /// fully anchored match against the whole path, forces the pattern to
+    /// always compile to `MatchStrategy::Regex` instead.
+    pub fn anchored(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.anchored = yes;
+        self
+    }
+
+    /// Toggle whether the compiled matcher runs against just a candidate's
+    /// basename, rather than its whole path. Defaults to `false`.
+    ///
+    /// The `BasenameLiteral` strategy already gets this behavior for free
+    /// when the pattern is a plain literal with no path separators (e.g.
+    /// `Makefile`); this option extends the same idea to patterns with
+    /// wildcards that would otherwise need `MatchStrategy::Regex` run
+    /// against the whole path, e.g. `test_*`.
+    ///
+    /// This only affects `GlobMatcher`, i.e. a single glob compiled via
+    /// `Glob::compile_matcher`. A `GlobSet` picks its own fast-path
+    /// strategies (literal, extension, regex, ...) based on a pattern's
+    /// shape and always runs them against the whole candidate path, so a
+    /// glob built with this option should not be added to a `GlobSet`.
+    pub fn basename_only(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.basename_only = yes;
+        self
+    }
+
+    /// Toggle whether the compiled matcher runs against just a candidate's
+    /// stem (its basename with the extension removed), rather than its
+    /// whole path. Defaults to `false`.
+    ///
+    /// This lets a pattern like `*_test` match `foo_test.rs` and
+    /// `foo_test.py` alike, regardless of extension, the same way
+    /// `basename_only` lets a pattern ignore the directory a file lives in.
+    ///
+    /// The same caveats as `basename_only` apply: this only affects
+    /// `GlobMatcher`, not `GlobSet`, which always matches whole candidate
+    /// paths.
+    pub fn stem_only(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.stem_only = yes;
+        self
+    }
+
+    /// Controls how `build` treats an empty pattern, e.g. `Glob::new("")`.
+    /// Defaults to `EmptyMode::MatchEmpty`, this crate's original behavior,
+    /// where the compiled `Glob` matches only the empty path.
+    pub fn empty_matches(&mut self, mode: EmptyMode) -> &mut GlobBuilder<'a> {
+        self.opts.empty_matches = mode;
+        self
+    }
+
+    /// Toggle whether `?` matches exactly one byte (the default) or one
+    /// full Unicode scalar value, which may be encoded as several bytes.
+    ///
+    /// This crate otherwise runs its compiled regex in byte-oriented,
+    /// non-Unicode mode unconditionally (see `Tokens::to_regex`), so a
+    /// multi-byte character like `é` needs two `?`s by default, one per
+    /// UTF-8 byte; set this to `false` if a single `?` should stand in for
+    /// one whole character instead, e.g. when matching filenames known to
+    /// be valid UTF-8. This has no effect on `*`, which always matches any
+    /// run of bytes regardless of character boundaries.
+    pub fn question_matches_bytes(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.question_matches_bytes = yes;
+        self
+    }
+
+    /// Toggle whether `*` refuses to match across a `.`, so `foo.*` matches
+    /// `foo.tar` but not `foo.tar.gz`, useful for archive-handling tools
+    /// that want the extension wildcard to stop at the first dot rather
+    /// than greedily consuming the rest of the name. Default is `false`,
+    /// i.e. `*` matches `.` like any other character.
+    ///
+    /// This excludes `.` from what `*` can match the same way
+    /// `literal_separator` excludes the path separator: uniformly,
+    /// everywhere in the pattern, not just its last component. The two
+    /// compose: with both enabled, `*` refuses to cross either character.
+    /// This has no effect on `?` (`Token::Any`), which already only
+    /// excludes a leading `.` and only when `match_leading_dot` is off.
+    pub fn star_stops_at_dot(&mut self, yes: bool) -> &mut GlobBuilder<'a> {
+        self.opts.star_stops_at_dot = yes;
+        self
+    }
+
+    /// Sets the size limit, in bytes, placed on this pattern's compiled
+    /// regex program.
+    ///
+    /// Raise this if `build` fails with `ErrorKind::Regex` on a pattern
+    /// that's merely large (e.g. a brace expansion with hundreds of
+    /// branches) rather than actually malformed. Default is 10 MiB.
+    pub fn regex_size_limit(&mut self, limit: usize) -> &mut GlobBuilder<'a> {
+        self.opts.regex_size_limit = limit;
+        self
+    }
+
+    /// Sets the size limit, in bytes, placed on this pattern's lazy DFA
+    /// cache. Default is 10 MiB.
+    pub fn dfa_size_limit(&mut self, limit: usize) -> &mut GlobBuilder<'a> {
+        self.opts.dfa_size_limit = limit;
+        self
+    }
+}
+
+/// Why a run of `parse_tokens` stopped.
+enum Stop {
+    /// The input was exhausted.
+    Eof,
+    /// A top-level `,` was found while parsing the body of `{...}`.
+    Comma,
+    /// A `}` was found, closing the enclosing `{...}`.
+    CloseBrace,
+    /// A top-level `|` was found while parsing the body of an extglob
+    /// group.
+    Pipe,
+    /// A `)` was found, closing the enclosing extglob group.
+    CloseParen,
+}
+
+/// The kind of group currently being parsed, if any. Entering a new `{...}`
+/// or extglob group while already inside one is rejected, mirroring the
+/// existing restriction against nested `{...}` alternates.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Group {
+    Alternate,
+    ExtGlob,
+}
+
+struct Parser<'a> {
+    glob: &'a str,
+    chars: ::std::iter::Peekable<str::Chars<'a>>,
+    backslash_escape: bool,
+    extended_glob: bool,
+    separator: char,
+}
+
+impl<'a> Parser<'a> {
+    fn new(
+        glob: &'a str,
+        backslash_escape: bool,
+        extended_glob: bool,
+        separator: char,
+    ) -> Parser<'a> {
+        Parser {
+            glob: glob,
+            chars: glob.chars().peekable(),
+            backslash_escape: backslash_escape,
+            extended_glob: extended_glob,
+            separator: separator,
+        }
+    }
+
+    /// The byte offset into `glob` of the next character to be parsed.
+    fn pos(&self) -> usize {
+        let remaining: String = self.chars.clone().collect();
+        self.glob.len() - remaining.len()
+    }
+
+    fn parse(mut self) -> Result<Tokens, Error> {
+        let (mut tokens, stop) = try!(self.parse_tokens(None));
+        match stop {
+            Stop::Eof => {}
+            Stop::CloseBrace => {
+                return Err(
+                    Error::from_kind(ErrorKind::UnopenedAlternates)
+                        .with_pos(self.pos()));
+            }
+            Stop::CloseParen | Stop::Comma | Stop::Pipe => unreachable!(),
         }
-        normalize_recursive(&mut tokens);
+        normalize_recursive(&mut tokens, self.separator);
         Ok(Tokens(tokens))
     }
 
+    /// Parses a run of tokens, stopping at EOF or, when `group` indicates
+    /// we're inside `{...}` or an extglob group, at the first top-level
+    /// separator or closing delimiter for that group.
+    fn parse_tokens(
+        &mut self,
+        group: Option<Group>,
+    ) -> Result<(Vec<Token>, Stop), Error> {
+        let mut tokens = vec![];
+        loop {
+            let c = match self.chars.next() {
+                Some(c) => c,
+                None => return Ok((tokens, Stop::Eof)),
+            };
+            match c {
+                '?' if self.peek_extglob_group() => {
+                    self.chars.next();
+                    let alts = try!(self.parse_extglob_group());
+                    tokens.push(Token::ExtGlob(ExtGlobKind::ZeroOrOne, alts));
+                }
+                '?' => tokens.push(Token::Any),
+                '*' => {
+                    if self.chars.peek() == Some(&'*') {
+                        self.chars.next();
+                        tokens.push(Token::RecursiveZeroOrMore);
+                    } else if self.peek_extglob_group() {
+                        self.chars.next();
+                        let alts = try!(self.parse_extglob_group());
+                        tokens.push(
+                            Token::ExtGlob(ExtGlobKind::ZeroOrMore, alts));
+                    } else {
+                        tokens.push(Token::ZeroOrMore);
+                    }
+                }
+                '+' if self.peek_extglob_group() => {
+                    self.chars.next();
+                    let alts = try!(self.parse_extglob_group());
+                    tokens.push(Token::ExtGlob(ExtGlobKind::OneOrMore, alts));
+                }
+                '@' if self.peek_extglob_group() => {
+                    self.chars.next();
+                    let alts = try!(self.parse_extglob_group());
+                    tokens.push(Token::ExtGlob(ExtGlobKind::ExactlyOne, alts));
+                }
+                '!' if self.peek_extglob_group() => {
+                    return Err(
+                        Error::from_kind(ErrorKind::UnsupportedExtGlobNegation)
+                            .with_pos(self.pos()));
+                }
+                '[' => tokens.push(try!(self.parse_class())),
+                '{' => {
+                    if group.is_some() {
+                        return Err(
+                            Error::from_kind(ErrorKind::NestedAlternates)
+                                .with_pos(self.pos()));
+                    }
+                    let alts = match try!(self.try_parse_range()) {
+                        Some(alts) => alts,
+                        None => try!(self.parse_alternates()),
+                    };
+                    tokens.push(Token::Alternates(alts));
+                }
+                '}' if group == Some(Group::Alternate) => {
+                    return Ok((tokens, Stop::CloseBrace));
+                }
+                '}' => {
+                    return Err(
+                        Error::from_kind(ErrorKind::UnopenedAlternates)
+                            .with_pos(self.pos()));
+                }
+                ',' if group == Some(Group::Alternate) => {
+                    return Ok((tokens, Stop::Comma));
+                }
+                ')' if group == Some(Group::ExtGlob) => {
+                    return Ok((tokens, Stop::CloseParen));
+                }
+                '|' if group == Some(Group::ExtGlob) => {
+                    return Ok((tokens, Stop::Pipe));
+                }
+                '\\' if self.backslash_escape => {
+                    match self.chars.peek().cloned() {
+                        Some('x') => {
+                            self.chars.next();
+                            tokens.push(
+                                Token::Literal(try!(self.parse_hex_escape())));
+                        }
+                        Some(d) if d.is_digit(8) => {
+                            self.chars.next();
+                            tokens.push(Token::Literal(
+                                try!(self.parse_octal_escape(d))));
+                        }
+                        Some(_) => tokens.push(
+                            Token::Literal(self.chars.next().unwrap())),
+                        None => tokens.push(Token::Literal('\\')),
+                    }
+                }
+                c => tokens.push(Token::Literal(c)),
+            }
+        }
+    }
+
+    /// Whether the character just consumed starts an extglob group, i.e.
+    /// extended globbing is enabled and the next character is `(`.
+    fn peek_extglob_group(&mut self) -> bool {
+        self.extended_glob && self.chars.peek() == Some(&'(')
+    }
+
+    /// Looks ahead for a bash-style numeric range `{start..end}`, having
+    /// already consumed the opening `{`. Handles both numeric ranges like
+    /// `{1..10}` and single-letter ranges like `{a..f}`, each optionally
+    /// followed by a `..step`, e.g. `{0..10..2}` or `{a..z..2}`. Returns
+    /// `Ok(None)` (consuming nothing) if the upcoming text isn't a range,
+    /// so the caller can fall back to treating `{...}` as an ordinary comma
+    /// separated alternate group.
+    fn try_parse_range(&mut self) -> Result<Option<Vec<Tokens>>, Error> {
+        let mut it = self.chars.clone();
+        let mut body = String::new();
+        loop {
+            match it.next() {
+                Some('}') => break,
+                Some(c) => body.push(c),
+                None => return Ok(None),
+            }
+        }
+        match parse_numeric_range_bounds(&body) {
+            Some(Ok((start, end, pad, step))) => {
+                self.chars = it;
+                return numeric_range_alternates(start, end, pad, step).map(Some);
+            }
+            Some(Err(err)) => {
+                self.chars = it;
+                return Err(err);
+            }
+            None => {}
+        }
+        match parse_alpha_range_bounds(&body) {
+            Some(Ok((start, end, step))) => {
+                self.chars = it;
+                Ok(Some(alpha_range_alternates(start, end, step)))
+            }
+            Some(Err(err)) => {
+                self.chars = it;
+                Err(err)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Parses the comma-separated body of a `{...}` alternate group, having
+    /// already consumed the opening `{`.
+    fn parse_alternates(&mut self) -> Result<Vec<Tokens>, Error> {
+        let mut alts = vec![];
+        loop {
+            let (mut toks, stop) = try!(self.parse_tokens(Some(Group::Alternate)));
+            // Each branch of a `{...}` is matched against a path
+            // independently of its siblings, so a leading/trailing `**/`
+            // or `/**` within one branch needs the same "zero or more
+            // directories" treatment `parse` gives the whole pattern,
+            // rather than staying a bare `Token::RecursiveZeroOrMore` that
+            // would otherwise require at least one directory component.
+            normalize_recursive(&mut toks, self.separator);
+            alts.push(Tokens(toks));
+            match stop {
+                Stop::Comma => continue,
+                Stop::CloseBrace => return Ok(alts),
+                Stop::Eof => {
+                    return Err(
+                        Error::from_kind(ErrorKind::UnclosedAlternates)
+                            .with_pos(self.pos()));
+                }
+                Stop::Pipe | Stop::CloseParen => unreachable!(),
+            }
+        }
+    }
+
+    /// Parses the `|`-separated body of an extglob group, having already
+    /// consumed the opening `(`.
+    fn parse_extglob_group(&mut self) -> Result<Vec<Tokens>, Error> {
+        let mut alts = vec![];
+        loop {
+            let (mut toks, stop) = try!(self.parse_tokens(Some(Group::ExtGlob)));
+            normalize_recursive(&mut toks, self.separator);
+            alts.push(Tokens(toks));
+            match stop {
+                Stop::Pipe => continue,
+                Stop::CloseParen => return Ok(alts),
+                Stop::Eof => {
+                    return Err(
+                        Error::from_kind(ErrorKind::UnclosedExtGlob)
+                            .with_pos(self.pos()));
+                }
+                Stop::Comma | Stop::CloseBrace => unreachable!(),
+            }
+        }
+    }
+
+    /// Parses a `\xHH` hex escape, assuming the `\x` has already been
+    /// consumed, into the literal byte it denotes.
+    fn parse_hex_escape(&mut self) -> Result<char, Error> {
+        let pos = self.pos();
+        let mut digits = String::new();
+        for _ in 0..2 {
+            match self.chars.next() {
+                Some(c) if c.is_digit(16) => digits.push(c),
+                _ => {
+                    return Err(
+                        Error::from_kind(
+                            ErrorKind::InvalidEscape(format!("\\x{}", digits)))
+                            .with_pos(pos));
+                }
+            }
+        }
+        let byte = u8::from_str_radix(&digits, 16).unwrap();
+        Ok(byte as char)
+    }
+
+    /// Parses a `\OOO` octal escape (one to three octal digits) into the
+    /// literal byte it denotes, assuming `\` and the first digit `first`
+    /// have already been consumed.
+    fn parse_octal_escape(&mut self, first: char) -> Result<char, Error> {
+        let pos = self.pos();
+        let mut digits = String::new();
+        digits.push(first);
+        for _ in 0..2 {
+            match self.chars.peek().cloned() {
+                Some(c) if c.is_digit(8) => {
+                    digits.push(c);
+                    self.chars.next();
+                }
+                _ => break,
+            }
+        }
+        match u32::from_str_radix(&digits, 8) {
+            Ok(value) if value <= 0xFF => Ok(value as u8 as char),
+            _ => Err(
+                Error::from_kind(
+                    ErrorKind::InvalidEscape(format!("\\{}", digits)))
+                    .with_pos(pos)),
+        }
+    }
+
+    /// Parses a `[...]` character class, assuming the opening `[` has
+    /// already been consumed.
+    ///
+    /// Following shell convention, `]` may appear as the first member of
+    /// the class (immediately after `[` or `[!`) to mean a literal `]`
+    /// instead of closing the class, e.g. `[]a]` matches `]` or `a`.
+    /// Likewise, `-` is treated as a literal hyphen rather than a range
+    /// operator when it appears as the last character before the closing
+    /// `]`, e.g. `[a-]` matches `a` or `-`.
     fn parse_class(&mut self) -> Result<Token, Error> {
+        // The `[` that starts this class has already been consumed, so its
+        // position is one byte back from here.
+        let open_pos = self.pos() - 1;
         let mut negated = false;
         if self.chars.peek() == Some(&'!') {
             negated = true;
             self.chars.next();
         }
-        let mut ranges = vec![];
+        let mut items = vec![];
         let mut first = true;
         loop {
             let c = match self.chars.next() {
                 Some(c) => c,
-                None => return Err(Error::UnclosedClass),
+                None => {
+                    return Err(
+                        Error::from_kind(ErrorKind::UnclosedClass)
+                            .with_pos(open_pos));
+                }
             };
             if c == ']' && !first {
                 break;
             }
             first = false;
+            if c == '[' && self.chars.peek() == Some(&':') {
+                if let Some(name) = try!(self.parse_posix_class()) {
+                    items.push(ClassItem::Named(name));
+                    continue;
+                }
+            }
             if self.chars.peek() == Some(&'-') {
                 let mut it = self.chars.clone();
                 it.next();
@@ -418,24 +1970,375 @@ impl<'a> Parser<'a> {
                         self.chars.next();
                         self.chars.next();
                         if c > end {
-                            return Err(Error::InvalidRange(c, end));
+                            return Err(
+                                Error::from_kind(ErrorKind::InvalidRange(c, end))
+                                    .with_pos(self.pos()));
                         }
-                        ranges.push((c, end));
+                        items.push(ClassItem::Range(c, end));
                     }
-                    _ => ranges.push((c, c)),
+                    _ => items.push(ClassItem::Range(c, c)),
                 }
             } else {
-                ranges.push((c, c));
+                items.push(ClassItem::Range(c, c));
+            }
+        }
+        Ok(Token::Class { negated: negated, items: items })
+    }
+
+    /// Attempts to parse a POSIX named class like `[:alpha:]`, assuming the
+    /// opening `[` has already been consumed and `:` is next. Returns the
+    /// class name on success, leaving the parser positioned just after the
+    /// closing `:]`. Returns `Ok(None)` (and consumes nothing) if what
+    /// follows doesn't look like a `[:name:]` class at all, so the `[` can
+    /// be treated as an ordinary literal instead. If it does look like one
+    /// but `name` isn't recognized, returns an error rather than silently
+    /// falling back, since `[:bogus:]` is almost certainly a typo rather
+    /// than an intentional literal.
+    fn parse_posix_class(&mut self) -> Result<Option<&'static str>, Error> {
+        let mut it = self.chars.clone();
+        if it.next() != Some(':') {
+            return Ok(None);
+        }
+        let mut name = String::new();
+        loop {
+            match it.next() {
+                Some(':') => match it.next() {
+                    Some(']') => break,
+                    _ => return Ok(None),
+                },
+                Some(c) => name.push(c),
+                None => return Ok(None),
+            }
+        }
+        let name = match name.as_str() {
+            "alnum" => "alnum",
+            "alpha" => "alpha",
+            "ascii" => "ascii",
+            "blank" => "blank",
+            "cntrl" => "cntrl",
+            "digit" => "digit",
+            "graph" => "graph",
+            "lower" => "lower",
+            "print" => "print",
+            "punct" => "punct",
+            "space" => "space",
+            "upper" => "upper",
+            "xdigit" => "xdigit",
+            _ => {
+                return Err(
+                    Error::from_kind(ErrorKind::UnrecognizedPosixClass(name))
+                        .with_pos(self.pos()));
+            }
+        };
+        self.chars = it;
+        Ok(Some(name))
+    }
+}
+
+/// The maximum number of members a `{start..end}` numeric range is allowed
+/// to expand to, so that a pattern like `{0..999999999}` returns an error
+/// instead of trying to allocate gigabytes of alternates.
+const MAX_NUMERIC_RANGE_LEN: u64 = 10_000;
+
+/// Splits the text following a range's first `..` into its `end` and an
+/// optional `step`, by looking for a second `..`. Returns `(end, None)` if
+/// there isn't one.
+fn split_range_step(rest: &str) -> (&str, Option<&str>) {
+    match rest.find("..") {
+        Some(i) => (&rest[..i], Some(&rest[i + 2..])),
+        None => (rest, None),
+    }
+}
+
+/// If `body` is exactly `start..end` or `start..end..step` where `start` and
+/// `end` are plain (optionally negative) integers and `step` (if present) is
+/// a plain integer, returns the parsed bounds, the zero-padded width to
+/// format members at if either side used leading zeros, and the step
+/// (defaulting to `1` when omitted). Returns `Some(Err(..))` if `step` is
+/// zero or negative. Returns `None` for anything else, including bodies
+/// containing a top-level `,`, so the caller can fall back to ordinary
+/// `{...}` alternate parsing.
+fn parse_numeric_range_bounds(
+    body: &str,
+) -> Option<Result<(i64, i64, Option<usize>, u64), Error>> {
+    if body.contains(',') {
+        return None;
+    }
+    let sep = match body.find("..") {
+        Some(i) => i,
+        None => return None,
+    };
+    let start = &body[..sep];
+    let (end, step) = split_range_step(&body[sep + 2..]);
+    if !is_plain_integer(start) || !is_plain_integer(end) {
+        return None;
+    }
+    let (start_n, end_n) = match (start.parse::<i64>(), end.parse::<i64>()) {
+        (Ok(start_n), Ok(end_n)) => (start_n, end_n),
+        _ => return None,
+    };
+    let step_n = match step {
+        None => 1,
+        Some(step) => match step.parse::<i64>() {
+            Ok(step_n) => step_n,
+            Err(_) => return None,
+        },
+    };
+    if step_n <= 0 {
+        return Some(Err(Error::from_kind(ErrorKind::InvalidRangeStep(step_n))));
+    }
+    let pad = if has_leading_zero(start) || has_leading_zero(end) {
+        Some(digit_len(start).max(digit_len(end)))
+    } else {
+        None
+    };
+    Some(Ok((start_n, end_n, pad, step_n as u64)))
+}
+
+/// Whether `s` is a non-empty run of ASCII digits, optionally preceded by a
+/// single `-`.
+fn is_plain_integer(s: &str) -> bool {
+    !digits_of(s).is_empty() && digits_of(s).chars().all(|c| c.is_ascii_digit())
+}
+
+/// Strips a single leading `-` sign, if present.
+fn digits_of(s: &str) -> &str {
+    if s.starts_with('-') { &s[1..] } else { s }
+}
+
+/// The number of digits in `s`, ignoring a leading `-` sign.
+fn digit_len(s: &str) -> usize {
+    digits_of(s).len()
+}
+
+/// Whether `s` is written with a zero-padded digit string, e.g. `01` or
+/// `-007`, as opposed to a bare `0` or an unpadded number.
+fn has_leading_zero(s: &str) -> bool {
+    let digits = digits_of(s);
+    digits.len() > 1 && digits.starts_with('0')
+}
+
+/// Expands an inclusive `{start..end}` range (in either direction), stepping
+/// by `step` each time, into the equivalent alternate branches, one literal
+/// per integer. As with bash, a `step` that doesn't evenly divide the span
+/// simply stops at the last member that doesn't overshoot `end`, rather than
+/// forcing a final member exactly at `end`. When `pad` is set, each member
+/// is formatted as a zero-padded digit string of that width instead of its
+/// bare decimal form.
+fn numeric_range_alternates(
+    start: i64,
+    end: i64,
+    pad: Option<usize>,
+    step: u64,
+) -> Result<Vec<Tokens>, Error> {
+    let raw_len = if start <= end {
+        (end - start) as u64
+    } else {
+        (start - end) as u64
+    };
+    let len = raw_len / step + 1;
+    if len > MAX_NUMERIC_RANGE_LEN {
+        return Err(Error::from_kind(ErrorKind::RangeTooLarge));
+    }
+    let mut alts = Vec::with_capacity(len as usize);
+    let mut n = start;
+    let step = step as i64;
+    loop {
+        let toks = format_range_member(n, pad).chars().map(Token::Literal)
+            .collect();
+        alts.push(Tokens(toks));
+        let next = if start <= end { n + step } else { n - step };
+        if (start <= end && next > end) || (start > end && next < end) {
+            break;
+        }
+        n = next;
+    }
+    Ok(alts)
+}
+
+/// Formats a single range member, zero-padding to `width` (preserving a `-`
+/// sign outside of the padding) when `pad` is set.
+fn format_range_member(n: i64, pad: Option<usize>) -> String {
+    match pad {
+        Some(width) => {
+            if n < 0 {
+                format!("-{:01$}", -n, width)
+            } else {
+                format!("{:01$}", n, width)
             }
         }
-        Ok(Token::Class { negated: negated, ranges: ranges })
+        None => n.to_string(),
+    }
+}
+
+/// If `body` is exactly `start..end` or `start..end..step` where `start` and
+/// `end` are a single ASCII letter of the same case and `step` (if present)
+/// is a plain integer, returns the parsed endpoints and the step (defaulting
+/// to `1` when omitted). Returns an `InvalidRange` error if `start` is
+/// lexically after `end`, an `InvalidRangeStep` error if `step` is zero or
+/// negative, and `None` for anything else, so the caller can fall back to
+/// ordinary `{...}` alternate parsing.
+fn parse_alpha_range_bounds(
+    body: &str,
+) -> Option<Result<(char, char, u64), Error>> {
+    if body.contains(',') {
+        return None;
+    }
+    let sep = match body.find("..") {
+        Some(i) => i,
+        None => return None,
+    };
+    let start = &body[..sep];
+    let (end, step) = split_range_step(&body[sep + 2..]);
+    let (start, end) = match (single_letter(start), single_letter(end)) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return None,
+    };
+    if start.is_lowercase() != end.is_lowercase() {
+        return None;
+    }
+    let step_n = match step {
+        None => 1,
+        Some(step) => match step.parse::<i64>() {
+            Ok(step_n) => step_n,
+            Err(_) => return None,
+        },
+    };
+    if step_n <= 0 {
+        return Some(Err(Error::from_kind(ErrorKind::InvalidRangeStep(step_n))));
+    }
+    if start > end {
+        return Some(Err(Error::from_kind(ErrorKind::InvalidRange(start, end))));
+    }
+    Some(Ok((start, end, step_n as u64)))
+}
+
+/// Returns the sole character in `s`, if `s` is a single ASCII letter.
+fn single_letter(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => Some(c),
+        _ => None,
+    }
+}
+
+/// Expands an inclusive `{start..end}` letter range, stepping by `step` each
+/// time, into the equivalent alternate branches, one literal per character.
+/// `start` must not be lexically after `end`. As with
+/// `numeric_range_alternates`, a `step` that doesn't evenly divide the span
+/// stops at the last member that doesn't overshoot `end`.
+fn alpha_range_alternates(start: char, end: char, step: u64) -> Vec<Tokens> {
+    let span = end as u32 - start as u32;
+    let mut alts = Vec::with_capacity((span as u64 / step) as usize + 1);
+    let mut c = start;
+    loop {
+        alts.push(Tokens(vec![Token::Literal(c)]));
+        let next = c as u32 + step as u32;
+        if next > end as u32 {
+            break;
+        }
+        c = (next as u8) as char;
+    }
+    alts
+}
+
+/// Collapses `./` components and repeated `/` out of a pattern's text, via
+/// `pathutil::collapse_dots_and_slashes`. Returns `pattern` unchanged (as
+/// a borrow) when there's nothing to collapse.
+fn collapse_pattern(pattern: &str) -> Cow<str> {
+    match collapse_dots_and_slashes(Cow::Borrowed(pattern.as_bytes())) {
+        Cow::Borrowed(_) => Cow::Borrowed(pattern),
+        // Only ASCII `.` and `/` bytes are ever added, removed, or moved,
+        // so the result is valid UTF-8 whenever the input was.
+        Cow::Owned(bytes) => Cow::Owned(String::from_utf8(bytes).unwrap()),
+    }
+}
+
+/// Strips leading and trailing ASCII whitespace from `pat`, for
+/// `GlobBuilder::trim`.
+///
+/// A trailing whitespace character preceded by an odd number of `\`s is
+/// escaped, so trimming stops there rather than continuing through it,
+/// leaving a pattern like `foo\ ` (a gitignore-style escaped trailing
+/// space) with its final space intact.
+fn trim_pattern_text(pat: &str, backslash_escape: bool) -> &str {
+    let pat = pat.trim_start();
+    if !backslash_escape {
+        return pat.trim_end();
+    }
+    let bytes = pat.as_bytes();
+    let mut end = bytes.len();
+    while end > 0 && (bytes[end - 1] as char).is_whitespace() {
+        let mut backslashes = 0;
+        while backslashes < end - 1 && bytes[end - 2 - backslashes] == b'\\' {
+            backslashes += 1;
+        }
+        if backslashes % 2 == 1 {
+            break;
+        }
+        end -= 1;
+    }
+    &pat[..end]
+}
+
+/// Strips a trailing `# comment` from `pat`, for
+/// `GlobBuilder::ignore_trailing_comment`.
+///
+/// A `#` only starts a comment when it isn't escaped with `\` and doesn't
+/// fall inside a `[...]` character class, so `[#]` and `\#` both still
+/// match a literal `#`.
+fn strip_trailing_comment(pat: &str, backslash_escape: bool) -> &str {
+    let mut in_class = false;
+    let mut escaped = false;
+    for (i, c) in pat.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if backslash_escape => escaped = true,
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            '#' if !in_class => return &pat[..i],
+            _ => {}
+        }
     }
+    pat
+}
+
+/// Checks that `pat` is a syntactically valid glob pattern, without paying
+/// for the regex compilation `GlobBuilder::build` also does.
+///
+/// This runs the same parsing and `**`-placement validation `build` does,
+/// against default `GlobOptions` (so, in particular, a leading `!` is not
+/// treated as negation, same as `Glob::new`), but stops there instead of
+/// translating the parsed tokens to a regex and compiling it, which is by
+/// far the most expensive part of building a `Glob`. This is meant for
+/// checking a large batch of glob strings quickly, e.g. in a linter, where
+/// only the pass/fail `Error` matters and no `Glob` needs to be kept around.
+///
+/// ```
+/// use globset::{validate, ErrorKind};
+///
+/// assert!(validate("src/**/*.rs").is_ok());
+/// assert_eq!(*validate("[abc").unwrap_err().kind(), ErrorKind::UnclosedClass);
+/// ```
+pub fn validate(pat: &str) -> Result<(), Error> {
+    let opts = GlobOptions::default();
+    let normalized = collapse_pattern(pat);
+    let sep = opts.separator.unwrap_or('/');
+    let mut tokens = try!(
+        Parser::new(&normalized, opts.backslash_escape, opts.extended_glob, sep)
+            .parse()
+            .map_err(|err| err.with_glob(pat)));
+    validate_globstar_placement(&mut tokens.0, opts.strict_globstar, sep)
+        .map_err(|err| err.with_glob(pat))
 }
 
-fn normalize_recursive(tokens: &mut Vec<Token>) {
+fn normalize_recursive(tokens: &mut Vec<Token>, sep: char) {
     if tokens.len() >= 2 {
         if tokens[0] == Token::RecursiveZeroOrMore
-            && tokens[1] == Token::Literal('/') {
+            && tokens[1] == Token::Literal(sep) {
             tokens.remove(1);
             tokens[0] = Token::RecursivePrefix;
         }
@@ -443,7 +2346,7 @@ fn normalize_recursive(tokens: &mut Vec<Token>) {
     if tokens.len() >= 2 {
         let last = tokens.len() - 1;
         if tokens[last] == Token::RecursiveZeroOrMore
-            && tokens[last - 1] == Token::Literal('/') {
+            && tokens[last - 1] == Token::Literal(sep) {
             tokens.remove(last - 1);
             let last = tokens.len() - 1;
             tokens[last] = Token::RecursiveSuffix;
@@ -451,73 +2354,1855 @@ fn normalize_recursive(tokens: &mut Vec<Token>) {
     }
 }
 
-impl Tokens {
-    fn to_regex(&self, opts: &GlobOptions) -> String {
-        let mut re = String::new();
-        re.push_str("(?-u)");
-        if opts.case_insensitive {
-            re.push_str("(?i)");
-        }
-        re.push('^');
-        for tok in &self.0 {
-            tok.push_regex(&mut re, opts);
-        }
-        re.push('$');
+/// Checks that every `**` in `tokens` is either the whole pattern or sits
+/// flanked by `/` (or the start/end of the pattern) on every side it needs
+/// it to, rejecting or demoting the ones that aren't.
+///
+/// `normalize_recursive` already rewrites the special-cased leading and
+/// trailing `**/`/`/**` into `RecursivePrefix`/`RecursiveSuffix`, so any
+/// `Token::RecursiveZeroOrMore` still in `tokens` by the time this runs is
+/// either the bare, whole-pattern `**`, a valid `.../**/...` sitting
+/// between two `/` literals, or a misplaced one like the `**` in `a**b` or
+/// `a/**b`. When `strict` is true, the misplaced case is rejected with
+/// `ErrorKind::InvalidRecursive`; otherwise it's demoted to two ordinary
+/// `*` wildcards, which is what a `**` degrades to in tools that don't
+/// treat it specially.
+///
+/// Recurses into each branch of a `Token::Alternates`/`Token::ExtGlob`,
+/// since `parse_alternates`/`parse_extglob_group` already ran
+/// `normalize_recursive` on each branch independently, so a `**` nested in
+/// one needs the same flanking check the top-level pattern gets, judged
+/// against that branch's own tokens rather than its siblings'.
+fn validate_globstar_placement(
+    tokens: &mut Vec<Token>,
+    strict: bool,
+    sep: char,
+) -> Result<(), Error> {
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i] {
+            Token::Alternates(ref mut alts) | Token::ExtGlob(_, ref mut alts) => {
+                for alt in alts.iter_mut() {
+                    try!(validate_globstar_placement(&mut alt.0, strict, sep));
+                }
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        if tokens[i] != Token::RecursiveZeroOrMore {
+            i += 1;
+            continue;
+        }
+        let flanked_left = i == 0 || tokens[i - 1] == Token::Literal(sep);
+        let flanked_right =
+            i == tokens.len() - 1 || tokens[i + 1] == Token::Literal(sep);
+        if flanked_left && flanked_right {
+            i += 1;
+            continue;
+        }
+        if strict {
+            return Err(Error::from_kind(ErrorKind::InvalidRecursive));
+        }
+        tokens[i] = Token::ZeroOrMore;
+        tokens.insert(i + 1, Token::ZeroOrMore);
+        i += 2;
+    }
+    Ok(())
+}
+
+/// Lowercases every `Token::Literal` in `tokens` in place, recursing into
+/// the branches of any `Alternates`/`ExtGlob` group.
+///
+/// Only ASCII case is folded, the same convention `Candidate::new_case_fold`
+/// uses elsewhere in this crate; a non-ASCII literal is left untouched.
+fn lowercase_literals(tokens: &mut Vec<Token>) {
+    for token in tokens.iter_mut() {
+        match *token {
+            Token::Literal(ref mut c) => *c = c.to_ascii_lowercase(),
+            Token::Alternates(ref mut alts) | Token::ExtGlob(_, ref mut alts) => {
+                for alt in alts.iter_mut() {
+                    lowercase_literals(&mut alt.0);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reports whether `chars` could be the start of some string these
+/// `tokens` fully match, for `GlobMatcher::is_prefix_match`.
+///
+/// See that method's doc comment for the ways this approximates rather
+/// than exactly implements the underlying token semantics.
+fn tokens_could_prefix_match(
+    tokens: &[Token],
+    chars: &[char],
+    opts: &GlobOptions,
+) -> bool {
+    if chars.is_empty() {
+        return true;
+    }
+    let (token, rest_tokens) = match tokens.split_first() {
+        Some(pair) => pair,
+        None => return false,
+    };
+    match *token {
+        Token::Literal(c) => {
+            let matches = if opts.case_insensitive {
+                chars[0].eq_ignore_ascii_case(&c)
+            } else {
+                chars[0] == c
+            };
+            matches
+                && tokens_could_prefix_match(rest_tokens, &chars[1..], opts)
+        }
+        Token::Any | Token::Class { .. } => {
+            tokens_could_prefix_match(rest_tokens, &chars[1..], opts)
+        }
+        Token::ZeroOrMore
+        | Token::RecursivePrefix
+        | Token::RecursiveSuffix
+        | Token::RecursiveZeroOrMore => {
+            (0..chars.len() + 1).any(|k| {
+                tokens_could_prefix_match(rest_tokens, &chars[k..], opts)
+            })
+        }
+        Token::Alternates(ref alts) => alts.iter().any(|alt| {
+            let mut combined = alt.0.clone();
+            combined.extend_from_slice(rest_tokens);
+            tokens_could_prefix_match(&combined, chars, opts)
+        }),
+        Token::ExtGlob(kind, ref alts) => {
+            let matched_alt = alts.iter().any(|alt| {
+                let mut combined = alt.0.clone();
+                combined.extend_from_slice(rest_tokens);
+                tokens_could_prefix_match(&combined, chars, opts)
+            });
+            if matched_alt {
+                return true;
+            }
+            match kind {
+                ExtGlobKind::ZeroOrOne | ExtGlobKind::ZeroOrMore => {
+                    tokens_could_prefix_match(rest_tokens, chars, opts)
+                }
+                ExtGlobKind::OneOrMore | ExtGlobKind::ExactlyOne => false,
+            }
+        }
+    }
+}
+
+/// Pushes the regex translation of `tokens` onto `re`, tracking whether
+/// each token begins a new path component so `Token::Any`/`ZeroOrMore` can
+/// honor `GlobOptions::match_leading_dot`. `at_start` is the component-start
+/// state to assume for the first token (the very start of the pattern, or
+/// whatever the caller of a nested alternation/extglob group carried in).
+fn push_regex_tokens(
+    tokens: &[Token],
+    re: &mut String,
+    opts: &GlobOptions,
+    start: bool,
+) {
+    let mut at_start = start;
+    for tok in tokens {
+        tok.push_regex(re, opts, at_start);
+        at_start = tok.starts_next_component(opts);
+    }
+}
+
+/// Escapes `member` so that it can be embedded as one branch of a `{...}`
+/// alternation, built by `Glob::alternation`, and matched literally.
+///
+/// Every character that the parser would otherwise treat specially inside
+/// or around an alternation (`\`, `*`, `?`, `[`, `{`, `}`, `,`) is prefixed
+/// with a `\`, which `GlobBuilder::backslash_escape` (always forced on by
+/// `Glob::alternation`, regardless of platform default) turns back into a
+/// literal character.
+/// Substitutes every `$VAR`/`${VAR}` placeholder in `pat` with its escaped
+/// value from `vars`, for `Glob::new_with_vars`.
+fn expand_vars(
+    pat: &str,
+    vars: &HashMap<String, String>,
+) -> Result<String, Error> {
+    let mut out = String::with_capacity(pat.len());
+    let mut chars = pat.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        let braced = chars.peek() == Some(&'{');
+        if braced {
+            chars.next();
+        }
+        let mut name = String::new();
+        if braced {
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => name.push(c),
+                    None => {
+                        return Err(Error::from_kind(
+                            ErrorKind::UndefinedVar(name)));
+                    }
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    name.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+        }
+        if name.is_empty() {
+            out.push('$');
+            continue;
+        }
+        match vars.get(&name) {
+            Some(value) => out.push_str(&escape_alternation_member(value)),
+            None => {
+                return Err(Error::from_kind(ErrorKind::UndefinedVar(name)));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn escape_alternation_member(member: &str) -> String {
+    let mut escaped = String::with_capacity(member.len());
+    for c in member.chars() {
+        match c {
+            '\\' | '*' | '?' | '[' | '{' | '}' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns this glob's separator character: `/`, unless overridden by
+/// `GlobBuilder::separator`.
+fn separator_char(opts: &GlobOptions) -> char {
+    opts.separator.unwrap_or('/')
+}
+
+/// Returns the regex character class matching a single character of a path
+/// component consumed by a bounded `**`, per `GlobBuilder::max_globstar_depth`:
+/// any character except this glob's separator (and `\` too, under
+/// `cross_platform_separators`).
+fn globstar_component_class(opts: &GlobOptions) -> String {
+    let mut excluded = ::regex::escape(&separator_char(opts).to_string());
+    if opts.cross_platform_separators && opts.separator.is_none() {
+        excluded.push_str(&::regex::escape("\\"));
+    }
+    format!("[^{}]", excluded)
+}
+
+/// Returns a regex matching 1 to `max` path components, joined by this
+/// glob's separator, for a bounded `**` (`GlobBuilder::max_globstar_depth`).
+fn bounded_component_run(opts: &GlobOptions, max: usize) -> String {
+    let class = globstar_component_class(opts);
+    let sep = ::regex::escape(&separator_char(opts).to_string());
+    let extra = max.saturating_sub(1);
+    format!("{}*(?:{}{}*){{0,{}}}", class, sep, class, extra)
+}
+
+/// Returns the regex fragment matching a single wildcard char (`Token::Any`)
+/// or repeated wildcard chars (with a `*` suffix, for `Token::ZeroOrMore`),
+/// honoring `literal_separator`/`cross_platform_separators`/`separator`
+/// (which character(s) are excluded) and `exclude_dot` (whether a leading
+/// `.` is excluded).
+fn wildcard_char_class(opts: &GlobOptions, exclude_dot: bool) -> String {
+    if !opts.literal_separator {
+        return if exclude_dot { "[^.]".to_string() } else { ".".to_string() };
+    }
+    let mut excluded = ::regex::escape(&separator_char(opts).to_string());
+    if opts.cross_platform_separators && opts.separator.is_none() {
+        excluded.push_str(&::regex::escape("\\"));
+    }
+    if exclude_dot {
+        excluded.push('.');
+    }
+    format!("[^{}]", excluded)
+}
+
+/// Returns the regex fragment matching a single Unicode scalar value,
+/// however many bytes its UTF-8 encoding takes, for `Token::Any` under
+/// `GlobBuilder::question_matches_bytes(false)`.
+///
+/// `exclude_dot`/`literal_separator`'s excluded characters are all ASCII
+/// (`.` and whatever `separator_char` returns), so they can only ever
+/// collide with the single-byte branch of this alternation; a multi-byte
+/// encoded scalar is never equal to an excluded ASCII byte and so is
+/// always left unrestricted here.
+fn wildcard_scalar_class(opts: &GlobOptions, exclude_dot: bool) -> String {
+    let mut excluded = String::new();
+    if opts.literal_separator {
+        excluded.push_str(&::regex::escape(&separator_char(opts).to_string()));
+        if opts.cross_platform_separators && opts.separator.is_none() {
+            excluded.push_str(&::regex::escape("\\"));
+        }
+    }
+    if exclude_dot {
+        excluded.push('.');
+    }
+    let ascii = format!("[^\\x80-\\xff{}]", excluded);
+    format!(
+        "(?:{}|[\\xc2-\\xdf][\\x80-\\xbf]|[\\xe0-\\xef][\\x80-\\xbf]{{2}}|[\\xf0-\\xf4][\\x80-\\xbf]{{3}})",
+        ascii,
+    )
+}
+
+/// Pushes an alternation matching every full Unicode case variant of `c`
+/// (its lowercase and uppercase mappings, alongside `c` itself), rather
+/// than relying on the regex engine's own `(?i)` flag.
+///
+/// This crate compiles patterns with `(?-u)` so that `.` and unanchored
+/// wildcards can match arbitrary, possibly non-UTF-8 bytes; under that
+/// mode, `(?i)` only folds ASCII case, so a non-ASCII literal (`case_insensitive
+/// with e.g. 'ß' or 'İ') needs its case variants spelled out explicitly.
+/// `char::to_lowercase`/`to_uppercase` implement full Unicode case
+/// mapping, so a single input char (like `ß`) can expand into a
+/// multi-character variant (like `SS`); each variant is emitted as its
+/// own escaped alternative.
+fn push_case_insensitive_literal(re: &mut String, c: char) {
+    let mut variants: Vec<String> = vec![c.to_string()];
+    let lower: String = c.to_lowercase().collect();
+    let upper: String = c.to_uppercase().collect();
+    if !variants.contains(&lower) {
+        variants.push(lower);
+    }
+    if !variants.contains(&upper) {
+        variants.push(upper);
+    }
+    if variants.len() == 1 {
+        re.push_str(&::regex::escape(&variants[0]));
+        return;
+    }
+    re.push_str("(?:");
+    for (i, variant) in variants.iter().enumerate() {
+        if i > 0 {
+            re.push('|');
+        }
+        re.push_str(&::regex::escape(variant));
+    }
+    re.push(')');
+}
+
+impl Tokens {
+    /// Translates these tokens into a regex, always in byte-oriented,
+    /// non-Unicode mode: there is no `GlobBuilder::unicode` toggle to turn
+    /// this off, since it isn't optional here the way it is in the `regex`
+    /// crate itself. `?`/`*` already need to match arbitrary path bytes,
+    /// including ones that aren't valid UTF-8, so this crate has run with
+    /// Unicode mode off unconditionally since it was first written; ASCII
+    /// paths already pay no Unicode-table overhead today.
+    fn to_regex(&self, opts: &GlobOptions) -> String {
+        let mut re = String::new();
+        re.push_str("(?-u)");
+        if opts.case_insensitive {
+            re.push_str("(?i)");
+        }
+        if opts.anchored {
+            re.push('^');
+        }
+        push_regex_tokens(&self.0, &mut re, opts, true);
+        if opts.anchored {
+            re.push('$');
+        }
         re
     }
 }
 
 impl Token {
-    fn push_regex(&self, re: &mut String, opts: &GlobOptions) {
+    /// Whether the character(s) matched by this token always end at a path
+    /// separator, so the token immediately following it starts a new path
+    /// component.
+    fn starts_next_component(&self, opts: &GlobOptions) -> bool {
+        match *self {
+            Token::Literal(c) if c == separator_char(opts) => true,
+            _ => false,
+        }
+    }
+
+    fn push_regex(&self, re: &mut String, opts: &GlobOptions, at_start: bool) {
         match *self {
+            Token::Literal('/') if opts.cross_platform_separators
+                && opts.separator.is_none() => {
+                re.push_str(r"[/\\]");
+            }
+            Token::Literal(c) if opts.case_insensitive && !c.is_ascii() => {
+                push_case_insensitive_literal(re, c);
+            }
             Token::Literal(c) => {
                 re.push_str(&::regex::escape(&c.to_string()));
             }
             Token::Any => {
-                if opts.literal_separator {
-                    re.push_str("[^/]");
+                let exclude_dot = !opts.match_leading_dot && at_start;
+                let cls = if opts.question_matches_bytes {
+                    wildcard_char_class(opts, exclude_dot)
                 } else {
-                    re.push_str(".");
+                    wildcard_scalar_class(opts, exclude_dot)
+                };
+                if opts.capture_groups {
+                    re.push('(');
+                    re.push_str(&cls);
+                    re.push(')');
+                } else {
+                    re.push_str(&cls);
                 }
             }
             Token::ZeroOrMore => {
-                if opts.literal_separator {
-                    re.push_str("[^/]*");
+                let exclude_dot =
+                    (!opts.match_leading_dot && at_start)
+                        || opts.star_stops_at_dot;
+                if opts.capture_groups {
+                    re.push('(');
+                    re.push_str(&wildcard_char_class(opts, exclude_dot));
+                    re.push_str("*)");
+                } else {
+                    re.push_str(&wildcard_char_class(opts, exclude_dot));
+                    re.push('*');
+                }
+            }
+            Token::RecursivePrefix => {
+                let sep = ::regex::escape(&separator_char(opts).to_string());
+                if let Some(depth) = opts.max_globstar_depth {
+                    re.push_str(&format!(
+                        "(?:{}{})?", bounded_component_run(opts, depth), sep));
+                } else if opts.cross_platform_separators && opts.separator.is_none() {
+                    re.push_str(r"(?:.*[/\\])?");
+                } else {
+                    re.push_str(&format!("(?:.*{})?", sep));
+                }
+            }
+            Token::RecursiveSuffix => {
+                let sep = ::regex::escape(&separator_char(opts).to_string());
+                if let Some(depth) = opts.max_globstar_depth {
+                    let run = bounded_component_run(opts, depth);
+                    if opts.globstar_matches_self {
+                        re.push_str(&format!("(?:{}{})?", sep, run));
+                    } else {
+                        re.push_str(&format!("{}{}", sep, run));
+                    }
+                    return;
+                }
+                let cross_platform =
+                    opts.cross_platform_separators && opts.separator.is_none();
+                match (cross_platform, opts.globstar_matches_self) {
+                    (true, true) => re.push_str(r"(?:[/\\].*)?"),
+                    (true, false) => re.push_str(r"[/\\].*"),
+                    (false, true) => re.push_str(&format!("(?:{}.*)?", sep)),
+                    (false, false) => re.push_str(&format!("{}.*", sep)),
+                }
+            }
+            Token::RecursiveZeroOrMore => {
+                if let Some(depth) = opts.max_globstar_depth {
+                    re.push_str(&format!("(?:{})?", bounded_component_run(opts, depth)));
                 } else {
                     re.push_str(".*");
                 }
             }
-            Token::RecursivePrefix => re.push_str("(?:.*/)?"),
-            Token::RecursiveSuffix => re.push_str("(?:/.*)?"),
-            Token::RecursiveZeroOrMore => re.push_str(".*"),
-            Token::Class { negated, ref ranges } => {
+            Token::Class { negated, ref items } => {
                 re.push('[');
                 if negated {
                     re.push('^');
                 }
-                for &(s, e) in ranges {
-                    if s == e {
-                        re.push_str(&::regex::escape(&s.to_string()));
-                    } else {
-                        re.push_str(&::regex::escape(&s.to_string()));
-                        re.push('-');
-                        re.push_str(&::regex::escape(&e.to_string()));
+                for item in items {
+                    match *item {
+                        ClassItem::Range(s, e) => {
+                            if s == e {
+                                re.push_str(&::regex::escape(&s.to_string()));
+                            } else {
+                                re.push_str(&::regex::escape(&s.to_string()));
+                                re.push('-');
+                                re.push_str(&::regex::escape(&e.to_string()));
+                            }
+                        }
+                        ClassItem::Named(name) => {
+                            re.push_str("[:");
+                            re.push_str(name);
+                            re.push_str(":]");
+                        }
                     }
                 }
                 re.push(']');
             }
             Token::Alternates(ref alts) => {
-                re.push_str("(?:");
+                re.push_str(if opts.capture_groups { "(" } else { "(?:" });
                 for (i, alt) in alts.iter().enumerate() {
                     if i > 0 {
                         re.push('|');
                     }
-                    for tok in &alt.0 {
-                        tok.push_regex(re, opts);
+                    push_regex_tokens(&alt.0, re, opts, at_start);
+                }
+                re.push(')');
+            }
+            Token::ExtGlob(kind, ref alts) => {
+                re.push_str("(?:");
+                for (i, alt) in alts.iter().enumerate() {
+                    if i > 0 {
+                        re.push('|');
                     }
+                    push_regex_tokens(&alt.0, re, opts, at_start);
                 }
                 re.push(')');
+                match kind {
+                    ExtGlobKind::ZeroOrOne => re.push('?'),
+                    ExtGlobKind::ZeroOrMore => re.push('*'),
+                    ExtGlobKind::OneOrMore => re.push('+'),
+                    ExtGlobKind::ExactlyOne => {}
+                }
             }
         }
     }
 }
+
+/// Serializes a `Glob` as the original glob string plus its builder options,
+/// so that deserializing re-parses and re-compiles the pattern rather than
+/// trying to (de)serialize the compiled regex.
+#[cfg(feature = "serde1")]
+mod serde_impl {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::de::Error as SerdeError;
+
+    use super::{Glob, GlobBuilder};
+
+    #[derive(Serialize, Deserialize)]
+    struct SerializedGlob {
+        glob: String,
+        literal_separator: bool,
+        case_insensitive: bool,
+        backslash_escape: bool,
+        extended_glob: bool,
+        negation: bool,
+        cross_platform_separators: bool,
+    }
+
+    impl Serialize for Glob {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where S: Serializer {
+            SerializedGlob {
+                glob: self.glob.clone(),
+                literal_separator: self.opts.literal_separator,
+                case_insensitive: self.opts.case_insensitive,
+                backslash_escape: self.opts.backslash_escape,
+                extended_glob: self.opts.extended_glob,
+                negation: self.opts.negation,
+                cross_platform_separators: self.opts.cross_platform_separators,
+            }.serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Glob {
+        fn deserialize<D>(deserializer: D) -> Result<Glob, D::Error>
+                where D: Deserializer<'de> {
+            let s = try!(SerializedGlob::deserialize(deserializer));
+            GlobBuilder::new(&s.glob)
+                .literal_separator(s.literal_separator)
+                .case_insensitive(s.case_insensitive)
+                .backslash_escape(s.backslash_escape)
+                .extended_glob(s.extended_glob)
+                .negation(s.negation)
+                .cross_platform_separators(s.cross_platform_separators)
+                .build()
+                .map_err(D::Error::custom)
+        }
+    }
+
+    // `GlobBuilder` borrows its glob string, so only `Serialize` makes
+    // sense here; deserializing one would require an owned glob to borrow
+    // from, which callers should get by deserializing a `Glob` instead.
+    impl<'a> Serialize for GlobBuilder<'a> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where S: Serializer {
+            SerializedGlob {
+                glob: self.glob.to_string(),
+                literal_separator: self.opts.literal_separator,
+                case_insensitive: self.opts.case_insensitive,
+                backslash_escape: self.opts.backslash_escape,
+                extended_glob: self.opts.extended_glob,
+                negation: self.opts.negation,
+                cross_platform_separators: self.opts.cross_platform_separators,
+            }.serialize(serializer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::{
+        validate, EmptyMode, Error, Glob, GlobBuilder, GlobCache, MatchStrategy,
+    };
+
+    #[cfg(feature = "serde1")]
+    #[test]
+    fn serde_round_trip_preserves_options() {
+        let glob = GlobBuilder::new("src/**/*.rs")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        let encoded = ::serde_json::to_string(&glob).unwrap();
+        let decoded: Glob = ::serde_json::from_str(&encoded).unwrap();
+        assert_eq!(glob, decoded);
+        assert!(decoded.compile_matcher().is_match("SRC/SUB/FOO.RS"));
+    }
+
+    #[cfg(feature = "serde1")]
+    #[test]
+    fn serde_round_trip_preserves_literal_separator_and_backslash_escape() {
+        let glob = GlobBuilder::new(r"src/\*/*.rs")
+            .literal_separator(true)
+            .backslash_escape(true)
+            .build()
+            .unwrap();
+        let encoded = ::serde_json::to_string(&glob).unwrap();
+        let decoded: Glob = ::serde_json::from_str(&encoded).unwrap();
+        assert_eq!(glob, decoded);
+        let matcher = decoded.compile_matcher();
+        // The escaped `\*` only matches a literal `*` path component, and
+        // literal_separator keeps the trailing `*` from crossing a `/`.
+        assert!(matcher.is_match("src/*/foo.rs"));
+        assert!(!matcher.is_match("src/sub/foo.rs"));
+        assert!(!matcher.is_match("src/*/sub/foo.rs"));
+    }
+
+    #[test]
+    fn glob_accessor_returns_the_original_pattern_string() {
+        assert_eq!(Glob::new("*.rs").unwrap().glob(), "*.rs");
+        assert_eq!(Glob::new("src/**/*.rs").unwrap().glob(), "src/**/*.rs");
+    }
+
+    #[test]
+    fn is_match_candidate_matches_one_candidate_against_several_matchers() {
+        use Candidate;
+
+        let rs = Glob::new("*.rs").unwrap().compile_matcher();
+        let md = Glob::new("*.md").unwrap().compile_matcher();
+        let candidate = Candidate::new("lib.rs");
+
+        assert!(rs.is_match_candidate(&candidate));
+        assert!(!md.is_match_candidate(&candidate));
+    }
+
+    #[test]
+    fn is_match_os_and_is_match_bytes_agree_with_is_match() {
+        use std::ffi::OsStr;
+
+        let matcher = Glob::new("src/*.rs").unwrap().compile_matcher();
+
+        assert_eq!(
+            matcher.is_match("src/lib.rs"),
+            matcher.is_match_os(OsStr::new("src/lib.rs")));
+        assert_eq!(
+            matcher.is_match("src/lib.rs"),
+            matcher.is_match_bytes(b"src/lib.rs"));
+
+        assert_eq!(
+            matcher.is_match("src/sub/lib.rs"),
+            matcher.is_match_os(OsStr::new("src/sub/lib.rs")));
+        assert_eq!(
+            matcher.is_match("src/sub/lib.rs"),
+            matcher.is_match_bytes(b"src/sub/lib.rs"));
+    }
+
+    #[test]
+    fn cross_platform_separators_accepts_backslash_as_a_separator() {
+        let glob = GlobBuilder::new("src/*.rs")
+            .literal_separator(true)
+            .cross_platform_separators(true)
+            .build()
+            .unwrap();
+        let matcher = glob.compile_matcher();
+        assert!(matcher.is_match("src/foo.rs"));
+        // A `\`-separated candidate (e.g. collected on Windows) matches too.
+        assert!(matcher.is_match(r"src\foo.rs"));
+        // literal_separator still stops the wildcard from crossing either
+        // separator.
+        assert!(!matcher.is_match("src/sub/foo.rs"));
+        assert!(!matcher.is_match(r"src\sub\foo.rs"));
+
+        let glob = GlobBuilder::new("**/*.rs")
+            .cross_platform_separators(true)
+            .build()
+            .unwrap();
+        assert!(glob.compile_matcher().is_match(r"a\b\c.rs"));
+    }
+
+    #[test]
+    fn from_str_parses_a_glob_with_default_options() {
+        let glob: Glob = "src/**/*.rs".parse().unwrap();
+        assert_eq!(glob, Glob::new("src/**/*.rs").unwrap());
+        assert!(glob.compile_matcher().is_match("src/sub/foo.rs"));
+
+        let err: Result<Glob, Error> = "src/{".parse();
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn is_literal_and_literal_detect_wildcard_free_globs() {
+        let glob = Glob::new("foo/bar.txt").unwrap();
+        assert!(glob.is_literal());
+        assert_eq!(glob.literal(), Some("foo/bar.txt"));
+
+        let glob = Glob::new("foo/*.txt").unwrap();
+        assert!(!glob.is_literal());
+        assert_eq!(glob.literal(), None);
+    }
+
+    #[test]
+    fn display_literal_unescapes_a_single_char_class_round_trip() {
+        let glob = Glob::new("[*].rs").unwrap();
+        assert!(glob.is_literal());
+        assert_eq!(glob.display_literal(), Some("*.rs".to_string()));
+
+        let glob = Glob::new("*.rs").unwrap();
+        assert!(!glob.is_literal());
+        assert_eq!(glob.display_literal(), None);
+    }
+
+    #[test]
+    fn alternation_members_may_contain_separators_and_globstars() {
+        let glob = Glob::new("{src/**/*.rs,tests/*.rs}").unwrap().compile_matcher();
+
+        assert!(glob.is_match("src/lib.rs"));
+        assert!(glob.is_match("src/a/b/foo.rs"));
+        assert!(glob.is_match("tests/basic.rs"));
+        assert!(!glob.is_match("other/basic.rs"));
+    }
+
+    #[test]
+    fn alternation_member_leading_globstar_matches_zero_directories() {
+        // Each branch of `{...}` is normalized independently, so a leading
+        // `**/` inside one still optionally matches zero directories, the
+        // same as it would outside any alternation.
+        let glob = Glob::new("{**/*.rs,*.md}").unwrap().compile_matcher();
+
+        assert!(glob.is_match("lib.rs"));
+        assert!(glob.is_match("src/lib.rs"));
+        assert!(glob.is_match("README.md"));
+    }
+
+    #[test]
+    fn literal_prefix_stops_at_first_wildcard() {
+        let glob = Glob::new("src/foo/*.rs").unwrap();
+        assert_eq!(glob.literal_prefix(), "src/foo/");
+
+        let glob = Glob::new("*.rs").unwrap();
+        assert_eq!(glob.literal_prefix(), "");
+
+        let glob = Glob::new("src/foo/bar.rs").unwrap();
+        assert_eq!(glob.literal_prefix(), "src/foo/bar.rs");
+    }
+
+    #[test]
+    fn regex_accessor_exposes_a_compilable_pattern() {
+        let glob = Glob::new("*.rs").unwrap();
+        let re = ::regex::bytes::Regex::new(glob.regex()).unwrap();
+        assert!(re.is_match(b"foo.rs"));
+        assert!(!re.is_match(b"foo.toml"));
+    }
+
+    #[test]
+    fn negation_flag_strips_leading_bang() {
+        let glob = GlobBuilder::new("!*.rs")
+            .negation(true)
+            .build()
+            .unwrap();
+        assert!(glob.is_negated());
+        let matcher = glob.compile_matcher();
+        assert!(matcher.is_match("foo.rs"));
+
+        // Off by default: a leading '!' is an ordinary literal character.
+        let glob = Glob::new("!*.rs").unwrap();
+        assert!(!glob.is_negated());
+        assert!(!glob.compile_matcher().is_match("foo.rs"));
+        assert!(glob.compile_matcher().is_match("!foo.rs"));
+
+        // A pattern that doesn't start with '!' is never negated, even
+        // with the option on.
+        let glob = GlobBuilder::new("*.rs").negation(true).build().unwrap();
+        assert!(!glob.is_negated());
+
+        // Escaping still works to match a literal leading '!' with the
+        // option enabled.
+        let glob = GlobBuilder::new(r"\!*.rs").negation(true).build().unwrap();
+        assert!(!glob.is_negated());
+        assert!(glob.compile_matcher().is_match("!foo.rs"));
+    }
+
+    #[test]
+    fn extglob_star_and_plus_repeat_the_group() {
+        let glob = GlobBuilder::new("+(ab).log")
+            .extended_glob(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("ab.log"));
+        assert!(glob.is_match("abab.log"));
+        assert!(!glob.is_match(".log"));
+
+        let glob = GlobBuilder::new("*(ab).log")
+            .extended_glob(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match(".log"));
+        assert!(glob.is_match("abab.log"));
+
+        // The repeated group can itself contain alternation.
+        let glob = GlobBuilder::new("+(foo|bar).log")
+            .extended_glob(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foobar.log"));
+        assert!(!glob.is_match(".log"));
+
+        // literal_separator still stops a `*` inside the group from
+        // crossing a path boundary.
+        let glob = GlobBuilder::new("+(a*)/b")
+            .extended_glob(true)
+            .literal_separator(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("axax/b"));
+        assert!(!glob.is_match("ax/ax/b"));
+    }
+
+    #[test]
+    fn extglob_negation_is_rejected() {
+        // `!(pattern)` would need to match any string the inner pattern
+        // *doesn't* match, i.e. a full-match complement. The `regex` crate
+        // this matcher compiles to has no lookaround (and no public way to
+        // complement an arbitrary compiled pattern), so there's no honest
+        // regex translation to fall back to. Reject clearly instead of
+        // silently compiling something that matches too much or too little.
+        let err = GlobBuilder::new("src/!(test).rs")
+            .extended_glob(true)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::UnsupportedExtGlobNegation);
+    }
+
+    #[test]
+    fn extglob_exactly_one_matches_a_single_alternative() {
+        let glob = GlobBuilder::new("@(foo|bar).rs")
+            .extended_glob(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo.rs"));
+        assert!(glob.is_match("bar.rs"));
+        assert!(!glob.is_match("baz.rs"));
+
+        // Subpatterns are full globs, so wildcards still work inside them.
+        let glob = GlobBuilder::new("@(*.rs|*.toml)")
+            .extended_glob(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("main.rs"));
+        assert!(glob.is_match("Cargo.toml"));
+        assert!(!glob.is_match("README.md"));
+
+        // With extended_glob off, '@', '(' and ')' stay literal.
+        let glob = Glob::new("@(foo|bar).rs").unwrap().compile_matcher();
+        assert!(!glob.is_match("foo.rs"));
+        assert!(glob.is_match("@(foo|bar).rs"));
+    }
+
+    #[test]
+    fn extended_glob_matches_alternation_operators() {
+        let glob = GlobBuilder::new("@(foo|bar).txt")
+            .extended_glob(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo.txt"));
+        assert!(glob.is_match("bar.txt"));
+        assert!(!glob.is_match("baz.txt"));
+
+        // Without extended_glob enabled, the extglob operators are literal.
+        let glob = Glob::new("@(foo|bar).txt").unwrap().compile_matcher();
+        assert!(!glob.is_match("foo.txt"));
+    }
+
+    #[cfg(feature = "serde1")]
+    #[test]
+    fn serde_round_trip_preserves_extended_glob() {
+        let glob = GlobBuilder::new("@(a|b)")
+            .extended_glob(true)
+            .build()
+            .unwrap();
+        let encoded = ::serde_json::to_string(&glob).unwrap();
+        let decoded: Glob = ::serde_json::from_str(&encoded).unwrap();
+
+        assert_eq!(glob, decoded);
+        let matcher = decoded.compile_matcher();
+        assert!(matcher.is_match("a"));
+        assert!(!matcher.is_match("@(a|b)"));
+    }
+
+    #[test]
+    fn numeric_brace_range_expands_to_alternation() {
+        let glob = Glob::new("file{1..3}.txt").unwrap().compile_matcher();
+        assert!(glob.is_match("file1.txt"));
+        assert!(glob.is_match("file2.txt"));
+        assert!(glob.is_match("file3.txt"));
+        assert!(!glob.is_match("file4.txt"));
+
+        // Descending and negative ranges are also expanded.
+        let glob = Glob::new("file{3..1}.txt").unwrap().compile_matcher();
+        assert!(glob.is_match("file2.txt"));
+        let glob = Glob::new("file{-1..1}.txt").unwrap().compile_matcher();
+        assert!(glob.is_match("file-1.txt"));
+
+        // A range too large to expand is a clear error, not an allocation.
+        let err = Glob::new("file{0..999999999}.txt").unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::RangeTooLarge);
+
+        // Anything that isn't a plain integer range still parses as an
+        // ordinary comma-separated alternate group.
+        let glob = Glob::new("{a,b..c}").unwrap().compile_matcher();
+        assert!(glob.is_match("a"));
+        assert!(glob.is_match("b..c"));
+    }
+
+    #[test]
+    fn numeric_brace_range_preserves_zero_padding() {
+        let glob = Glob::new("page{01..12}.png").unwrap().compile_matcher();
+        assert!(glob.is_match("page01.png"));
+        assert!(glob.is_match("page09.png"));
+        assert!(glob.is_match("page12.png"));
+        assert!(!glob.is_match("page1.png"));
+
+        // A padded endpoint on either side sets the width.
+        let glob = Glob::new("page{1..010}.png").unwrap().compile_matcher();
+        assert!(glob.is_match("page001.png"));
+        assert!(!glob.is_match("page1.png"));
+
+        // Without any padded bound, members stay unpadded.
+        let glob = Glob::new("page{1..3}.png").unwrap().compile_matcher();
+        assert!(glob.is_match("page1.png"));
+        assert!(!glob.is_match("page01.png"));
+    }
+
+    #[test]
+    fn alpha_brace_range_expands_to_alternation() {
+        let glob = Glob::new("data/{a..e}/*.bin").unwrap().compile_matcher();
+        assert!(glob.is_match("data/a/x.bin"));
+        assert!(glob.is_match("data/e/x.bin"));
+        assert!(!glob.is_match("data/f/x.bin"));
+
+        let glob = Glob::new("shard{A..C}").unwrap().compile_matcher();
+        assert!(glob.is_match("shardB"));
+        assert!(!glob.is_match("shardD"));
+
+        // Mixed-case endpoints aren't a letter range, so they fall back to
+        // an ordinary two-branch alternate.
+        let glob = Glob::new("{A..c}").unwrap().compile_matcher();
+        assert!(glob.is_match("A..c"));
+
+        let err = Glob::new("{f..a}").unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::InvalidRange('f', 'a'));
+    }
+
+    #[test]
+    fn numeric_brace_range_supports_a_step() {
+        let glob = Glob::new("frame{0..10..2}.png").unwrap().compile_matcher();
+        assert!(glob.is_match("frame0.png"));
+        assert!(glob.is_match("frame2.png"));
+        assert!(glob.is_match("frame10.png"));
+        assert!(!glob.is_match("frame1.png"));
+        assert!(!glob.is_match("frame11.png"));
+
+        // A step that doesn't evenly divide the span stops short of `end`
+        // rather than forcing a final member exactly at `end`, just as bash
+        // does.
+        let glob = Glob::new("frame{0..10..3}.png").unwrap().compile_matcher();
+        assert!(glob.is_match("frame9.png"));
+        assert!(!glob.is_match("frame10.png"));
+
+        // Descending ranges step downward.
+        let glob = Glob::new("frame{10..0..5}.png").unwrap().compile_matcher();
+        assert!(glob.is_match("frame10.png"));
+        assert!(glob.is_match("frame5.png"));
+        assert!(glob.is_match("frame0.png"));
+        assert!(!glob.is_match("frame7.png"));
+
+        // A zero or wrong-sign step is a clear error.
+        let err = Glob::new("frame{0..10..0}.png").unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::InvalidRangeStep(0));
+        let err = Glob::new("frame{0..10..-2}.png").unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::InvalidRangeStep(-2));
+    }
+
+    #[test]
+    fn alpha_brace_range_supports_a_step() {
+        let glob = Glob::new("shard{a..g..2}").unwrap().compile_matcher();
+        assert!(glob.is_match("sharda"));
+        assert!(glob.is_match("shardc"));
+        assert!(glob.is_match("shardg"));
+        assert!(!glob.is_match("shardb"));
+        assert!(!glob.is_match("shardd"));
+
+        // A zero or wrong-sign step is a clear error.
+        let err = Glob::new("{a..z..0}").unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::InvalidRangeStep(0));
+        let err = Glob::new("{a..z..-2}").unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::InvalidRangeStep(-2));
+    }
+
+    #[test]
+    fn posix_named_classes_mix_with_literals_and_ranges() {
+        let glob = Glob::new("[[:alpha:]_0-9].txt").unwrap().compile_matcher();
+        assert!(glob.is_match("a.txt"));
+        assert!(glob.is_match("_.txt"));
+        assert!(glob.is_match("5.txt"));
+        assert!(!glob.is_match("!.txt"));
+
+        let glob = Glob::new("[![:digit:]].txt").unwrap().compile_matcher();
+        assert!(glob.is_match("a.txt"));
+        assert!(!glob.is_match("5.txt"));
+    }
+
+    #[test]
+    fn class_combines_multiple_ranges_and_literals() {
+        let glob = Glob::new("[a-zA-Z0-9_].txt").unwrap().compile_matcher();
+        assert!(glob.is_match("c.txt"));
+        assert!(glob.is_match("Z.txt"));
+        assert!(glob.is_match("4.txt"));
+        assert!(glob.is_match("_.txt"));
+        assert!(!glob.is_match("-.txt"));
+        assert!(!glob.is_match("!.txt"));
+
+        let glob = Glob::new("[!a-z0-9].txt").unwrap().compile_matcher();
+        assert!(!glob.is_match("c.txt"));
+        assert!(!glob.is_match("4.txt"));
+        assert!(glob.is_match("_.txt"));
+    }
+
+    #[test]
+    fn case_insensitive_folds_non_ascii_unicode() {
+        let glob = GlobBuilder::new("straße.txt")
+            .case_insensitive(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("straße.txt"));
+        assert!(glob.is_match("STRAßE.txt"));
+        assert!(!glob.is_match("strasse.txt"));
+    }
+
+    #[test]
+    fn lowercase_folds_literal_portions_of_the_pattern() {
+        let glob = GlobBuilder::new("*.TXT")
+            .lowercase(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo.txt"));
+        // The caller is responsible for lowercasing candidates; an
+        // unlowered candidate simply doesn't match.
+        assert!(!glob.is_match("foo.TXT"));
+    }
+
+    #[test]
+    fn lowercase_folds_literals_inside_alternation_branches() {
+        let glob = GlobBuilder::new("{FOO,BAR}.txt")
+            .lowercase(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo.txt"));
+        assert!(glob.is_match("bar.txt"));
+        assert!(!glob.is_match("FOO.txt"));
+    }
+
+    #[test]
+    fn match_leading_dot_can_be_disabled() {
+        let glob = GlobBuilder::new("*")
+            .match_leading_dot(false)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(!glob.is_match(".gitignore"));
+        assert!(glob.is_match("lib.rs"));
+
+        let glob = GlobBuilder::new(".*")
+            .match_leading_dot(false)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match(".gitignore"));
+
+        let glob = Glob::new("*").unwrap().compile_matcher();
+        assert!(glob.is_match(".gitignore"));
+    }
+
+    #[test]
+    fn directory_matching_strips_trailing_slash_and_gates_is_match_dir() {
+        let glob = GlobBuilder::new("build/")
+            .directory_matching(true)
+            .build()
+            .unwrap();
+        assert!(glob.is_dir_only());
+        let matcher = glob.compile_matcher();
+        assert!(matcher.is_match_dir("build", true));
+        assert!(!matcher.is_match_dir("build", false));
+        assert!(matcher.is_match("build"));
+
+        let glob = GlobBuilder::new("build/").build().unwrap();
+        assert!(!glob.is_dir_only());
+    }
+
+    #[test]
+    fn hex_and_octal_escapes_produce_literal_bytes() {
+        let glob = GlobBuilder::new("a\\x09b")
+            .backslash_escape(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("a\tb"));
+        assert!(!glob.is_match("ab"));
+
+        let glob = GlobBuilder::new("a\\012b")
+            .backslash_escape(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("a\nb"));
+
+        let err = GlobBuilder::new("a\\xZZb")
+            .backslash_escape(true)
+            .build()
+            .unwrap_err();
+        match *err.kind() {
+            super::ErrorKind::InvalidEscape(_) => {}
+            ref kind => panic!("unexpected error kind: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn backslash_escape_makes_a_literal_metacharacter() {
+        let glob = GlobBuilder::new("\\*.rs")
+            .backslash_escape(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("*.rs"));
+        assert!(!glob.is_match("lib.rs"));
+
+        let glob = GlobBuilder::new("\\*.rs")
+            .backslash_escape(false)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(!glob.is_match("*.rs"));
+        assert!(glob.is_match("\\lib.rs"));
+        assert!(!glob.is_match("lib.rs"));
+    }
+
+    #[test]
+    fn class_allows_literal_bracket_and_hyphen() {
+        let glob = Glob::new("[]].txt").unwrap().compile_matcher();
+        assert!(glob.is_match("].txt"));
+        assert!(!glob.is_match("a.txt"));
+
+        let glob = Glob::new("[a-].txt").unwrap().compile_matcher();
+        assert!(glob.is_match("a.txt"));
+        assert!(glob.is_match("-.txt"));
+        assert!(!glob.is_match("b.txt"));
+    }
+
+    #[test]
+    fn unrecognized_posix_class_is_an_error() {
+        let err = Glob::new("[[:bogus:]].txt").unwrap_err();
+        match *err.kind() {
+            super::ErrorKind::UnrecognizedPosixClass(ref name) => {
+                assert_eq!(name, "bogus");
+            }
+            ref kind => panic!("unexpected error kind: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn unclosed_class_error_reports_the_position_of_the_open_bracket() {
+        let err = Glob::new("foo/[abc.txt").unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::UnclosedClass);
+        assert_eq!(err.pos(), Some(4));
+
+        let err = Glob::new("[").unwrap_err();
+        assert_eq!(err.pos(), Some(0));
+    }
+
+    #[test]
+    fn matches_paths_filters_a_slice_by_a_single_glob() {
+        let glob = Glob::new("*.rs").unwrap().compile_matcher();
+        let paths = vec!["foo.rs", "foo.c", "bar.rs", "README.md"];
+
+        let matched = glob.matches_paths(&paths);
+        assert_eq!(matched, vec![&"foo.rs", &"bar.rs"]);
+    }
+
+    #[test]
+    fn validate_catches_syntax_errors_without_compiling_a_regex() {
+        let err = validate("[abc").unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::UnclosedClass);
+        // `validate` never builds a `Glob`, so there's no compiled regex
+        // to inspect either way, but `Glob::new` on the same pattern must
+        // report the exact same error kind and position.
+        assert_eq!(err.pos(), Glob::new("[abc").unwrap_err().pos());
+
+        assert!(validate("src/**/*.rs").is_ok());
+    }
+
+    #[test]
+    fn force_regex_still_matches_correctly() {
+        let glob = GlobBuilder::new("*.rs").build().unwrap();
+        assert_eq!(MatchStrategy::new(&glob), MatchStrategy::RequiredExtension(
+            ::std::ffi::OsString::from("rs")));
+
+        let forced = GlobBuilder::new("*.rs")
+            .force_regex(true)
+            .build()
+            .unwrap();
+        assert_eq!(MatchStrategy::new(&forced), MatchStrategy::Regex);
+
+        let matcher = forced.compile_matcher();
+        assert!(matcher.is_match("lib.rs"));
+        assert!(!matcher.is_match("lib.c"));
+    }
+
+    #[test]
+    fn alternation_of_pure_literals_uses_the_literals_strategy() {
+        let glob = GlobBuilder::new("{foo,bar,baz}.txt").build().unwrap();
+        assert_eq!(MatchStrategy::new(&glob), MatchStrategy::Literals(vec![
+            "foo.txt".to_string(),
+            "bar.txt".to_string(),
+            "baz.txt".to_string(),
+        ]));
+
+        let matcher = glob.compile_matcher();
+        assert!(matcher.is_match("foo.txt"));
+        assert!(matcher.is_match("bar.txt"));
+        assert!(matcher.is_match("baz.txt"));
+        assert!(!matcher.is_match("quux.txt"));
+
+        // A branch containing a wildcard falls back to the regex strategy,
+        // same as before this optimization existed.
+        let glob = GlobBuilder::new("{foo,*}.txt").build().unwrap();
+        assert_eq!(MatchStrategy::new(&glob), MatchStrategy::Regex);
+    }
+
+    #[test]
+    fn compound_extension_matches_a_multi_dot_suffix() {
+        let glob = GlobBuilder::new("*.tar.gz").build().unwrap();
+        assert_eq!(MatchStrategy::new(&glob), MatchStrategy::CompoundExtension(
+            ".tar.gz".to_string()));
+
+        let matcher = glob.compile_matcher();
+        assert!(matcher.is_match("a.tar.gz"));
+        assert!(matcher.is_match("archive/backup.tar.gz"));
+        assert!(!matcher.is_match("a.gz"));
+        assert!(!matcher.is_match("a.targz"));
+
+        // A single-segment suffix isn't "compound"; it's left to the
+        // ordinary extension strategies.
+        let glob = GlobBuilder::new("*.rs").build().unwrap();
+        assert_ne!(MatchStrategy::new(&glob),
+            MatchStrategy::CompoundExtension(".rs".to_string()));
+    }
+
+    #[test]
+    fn regex_size_limit_can_be_raised_to_compile_large_patterns() {
+        let branches: Vec<String> =
+            (0..200).map(|i| format!("branch{}", i)).collect();
+        let pat = format!("{{{}}}", branches.join(","));
+
+        let err = GlobBuilder::new(&pat)
+            .regex_size_limit(16)
+            .build()
+            .unwrap_err();
+        match *err.kind() {
+            super::ErrorKind::Regex(_) => {}
+            ref kind => panic!("unexpected error kind: {:?}", kind),
+        }
+
+        let glob = GlobBuilder::new(&pat)
+            .regex_size_limit(1 << 20)
+            .build()
+            .unwrap();
+        assert!(glob.compile_matcher().is_match("branch5"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn from_os_str_builds_a_glob_from_valid_utf8_bytes() {
+        use std::ffi::OsStr;
+
+        let glob = Glob::from_os_str(OsStr::new("*.rs")).unwrap();
+        assert!(glob.compile_matcher().is_match("lib.rs"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn from_os_str_rejects_non_utf8_bytes() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        // 0xFF is never valid UTF-8, on its own or as a continuation byte.
+        let bytes = [b'*', b'.', 0xFF];
+        let os_str = OsStr::from_bytes(&bytes);
+
+        let err = Glob::from_os_str(os_str).unwrap_err();
+        match *err.kind() {
+            super::ErrorKind::InvalidUtf8 => {}
+            ref kind => panic!("unexpected error kind: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn strict_globstar_rejects_a_misplaced_double_star_by_default() {
+        let err = GlobBuilder::new("a/**b").build().unwrap_err();
+        match *err.kind() {
+            super::ErrorKind::InvalidRecursive => {}
+            ref kind => panic!("unexpected error kind: {:?}", kind),
+        }
+
+        // Well-placed `**` is unaffected.
+        assert!(GlobBuilder::new("a/**/b").build().is_ok());
+        assert!(GlobBuilder::new("**").build().is_ok());
+    }
+
+    #[test]
+    fn strict_globstar_disabled_demotes_to_two_single_stars() {
+        let glob = GlobBuilder::new("a/**b")
+            .strict_globstar(false)
+            .literal_separator(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        // `**` demoted to `*` `*` behaves like the ordinary single-star
+        // wildcard: it matches within a path component but not across `/`.
+        assert!(glob.is_match("a/xyb"));
+        assert!(glob.is_match("a/b"));
+        assert!(!glob.is_match("a/x/yb"));
+    }
+
+    #[test]
+    fn equality_is_syntactic_not_semantic() {
+        // These compile to the same regex and match the same paths, but
+        // aren't the same pattern text, so they aren't `==`.
+        let dotted = Glob::new("./foo").unwrap();
+        let plain = Glob::new("foo").unwrap();
+        assert_ne!(dotted, plain);
+
+        assert_eq!(Glob::new("foo").unwrap(), Glob::new("foo").unwrap());
+    }
+
+    #[test]
+    fn redundant_dots_and_slashes_are_normalized_on_both_sides() {
+        // A doubled separator or a `./` component in the candidate path
+        // matches a glob written without them...
+        let glob = Glob::new("src/foo/bar.rs").unwrap().compile_matcher();
+        assert!(glob.is_match("src//foo/./bar.rs"));
+
+        // ...and the same holds in reverse, since the pattern text itself
+        // is normalized the same way before it's compiled.
+        let glob = Glob::new("src//./foo/bar.rs").unwrap().compile_matcher();
+        assert!(glob.is_match("src/foo/bar.rs"));
+    }
+
+    #[test]
+    fn empty_alternate_branches_match_the_empty_string() {
+        let glob = Glob::new("file{,_test}.rs").unwrap().compile_matcher();
+        assert!(glob.is_match("file.rs"));
+        assert!(glob.is_match("file_test.rs"));
+        assert!(!glob.is_match("file_other.rs"));
+
+        // A leading, trailing, or doubled comma each produce an empty
+        // branch, not a parse error.
+        let glob = Glob::new("{a,,b}").unwrap().compile_matcher();
+        assert!(glob.is_match("a"));
+        assert!(glob.is_match(""));
+        assert!(glob.is_match("b"));
+        assert!(!glob.is_match("c"));
+    }
+
+    #[test]
+    fn leading_recursive_prefix_matches_zero_directories() {
+        let glob = Glob::new("**/foo").unwrap().compile_matcher();
+        assert!(glob.is_match("foo"));
+        assert!(glob.is_match("bar/foo"));
+        assert!(glob.is_match("bar/baz/foo"));
+    }
+
+    #[test]
+    fn trailing_recursive_suffix_matches_the_directory_itself_by_default() {
+        let glob = Glob::new("foo/**").unwrap().compile_matcher();
+        assert!(glob.is_match("foo"));
+        assert!(glob.is_match("foo/bar"));
+        assert!(glob.is_match("foo/bar/baz"));
+    }
+
+    #[test]
+    fn globstar_matches_self_disabled_requires_at_least_one_component() {
+        let glob = GlobBuilder::new("foo/**")
+            .globstar_matches_self(false)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(!glob.is_match("foo"));
+        assert!(glob.is_match("foo/bar"));
+        assert!(glob.is_match("foo/bar/baz"));
+    }
+
+    #[test]
+    fn find_returns_the_whole_path_since_globs_are_fully_anchored() {
+        let glob = Glob::new("*.rs").unwrap().compile_matcher();
+        assert_eq!(glob.find("main.rs"), Some((0, 7)));
+        assert_eq!(glob.find("main.txt"), None);
+    }
+
+    #[test]
+    fn capture_groups_reports_what_each_wildcard_matched() {
+        let glob = GlobBuilder::new("img-*.png")
+            .capture_groups(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert_eq!(
+            glob.captures("img-hello.png"),
+            Some(vec!["hello".to_string()]));
+        assert_eq!(glob.captures("img-hello.jpg"), None);
+    }
+
+    #[test]
+    fn anchored_disabled_matches_anywhere_in_the_path() {
+        let glob = GlobBuilder::new("node_modules")
+            .anchored(false)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("a/node_modules/b"));
+        assert!(glob.is_match("node_modules"));
+        assert!(!glob.is_match("a/vendor/b"));
+
+        assert_eq!(
+            MatchStrategy::new(glob.glob()),
+            MatchStrategy::Regex);
+    }
+
+    #[test]
+    fn basename_only_matches_against_just_the_basename() {
+        let glob = GlobBuilder::new("test_*")
+            .basename_only(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("a/b/test_foo.rs"));
+        assert!(!glob.is_match("a/test_foo.rs/b"));
+    }
+
+    #[test]
+    fn stem_only_matches_against_the_basename_with_extension_stripped() {
+        let glob = GlobBuilder::new("*_test")
+            .stem_only(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo_test.rs"));
+        assert!(glob.is_match("src/foo_test.py"));
+        assert!(!glob.is_match("foo_test_helper.rs"));
+    }
+
+    #[test]
+    fn empty_matches_defaults_to_matching_only_the_empty_path() {
+        let glob = Glob::new("").unwrap().compile_matcher();
+        assert!(glob.is_match(""));
+        assert!(!glob.is_match("a"));
+    }
+
+    #[test]
+    fn empty_matches_error_rejects_an_empty_pattern() {
+        let err = GlobBuilder::new("")
+            .empty_matches(EmptyMode::Error)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), &super::ErrorKind::EmptyGlob);
+    }
+
+    #[test]
+    fn empty_matches_nothing_never_matches_any_path() {
+        let glob = GlobBuilder::new("")
+            .empty_matches(EmptyMode::MatchNothing)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(!glob.is_match(""));
+        assert!(!glob.is_match("a"));
+        assert!(!glob.is_match("anything/at/all.txt"));
+    }
+
+    #[test]
+    fn build_cached_reuses_the_entry_for_an_unchanged_pattern_and_options() {
+        let mut cache = GlobCache::new();
+        let a = GlobBuilder::new("*.rs").build_cached(&mut cache).unwrap();
+        assert_eq!(cache.len(), 1);
+        let b = GlobBuilder::new("*.rs").build_cached(&mut cache).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(a, b);
+        assert!(a.compile_matcher().is_match("main.rs"));
+    }
+
+    #[test]
+    fn build_cached_treats_different_options_as_a_different_entry() {
+        let mut cache = GlobCache::new();
+        GlobBuilder::new("*.rs").build_cached(&mut cache).unwrap();
+        GlobBuilder::new("*.rs")
+            .case_insensitive(true)
+            .build_cached(&mut cache)
+            .unwrap();
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn build_cached_treats_different_patterns_as_a_different_entry() {
+        let mut cache = GlobCache::new();
+        GlobBuilder::new("*.rs").build_cached(&mut cache).unwrap();
+        GlobBuilder::new("*.txt").build_cached(&mut cache).unwrap();
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn new_with_vars_substitutes_bare_and_braced_placeholders() {
+        let mut vars = super::HashMap::new();
+        vars.insert("HOME".to_string(), "/home/bob".to_string());
+        vars.insert("EXT".to_string(), "conf".to_string());
+
+        let glob = Glob::new_with_vars("$HOME/*.${EXT}", &vars)
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("/home/bob/app.conf"));
+        assert!(!glob.is_match("/home/alice/app.conf"));
+    }
+
+    #[test]
+    fn new_with_vars_escapes_metacharacters_in_the_substituted_value() {
+        let mut vars = super::HashMap::new();
+        vars.insert("DIR".to_string(), "a[b]*c".to_string());
+
+        let glob = Glob::new_with_vars("$DIR/*.txt", &vars)
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("a[b]*c/notes.txt"));
+        assert!(!glob.is_match("ab/notes.txt"));
+    }
+
+    #[test]
+    fn new_with_vars_errors_on_an_undefined_variable() {
+        let vars = super::HashMap::new();
+        let err = Glob::new_with_vars("$MISSING/*.txt", &vars).unwrap_err();
+        assert_eq!(
+            err.kind(), &super::ErrorKind::UndefinedVar("MISSING".to_string()));
+    }
+
+    #[test]
+    fn is_prefix_match_accepts_a_partial_typed_path() {
+        let glob = Glob::new("src/*.rs").unwrap().compile_matcher();
+        assert!(glob.is_prefix_match("src/l"));
+        assert!(glob.is_prefix_match("src/lib.rs"));
+        assert!(glob.is_prefix_match("src/"));
+        assert!(glob.is_prefix_match(""));
+    }
+
+    #[test]
+    fn is_prefix_match_rejects_a_path_whose_literal_prefix_conflicts() {
+        let glob = Glob::new("src/*.rs").unwrap().compile_matcher();
+        assert!(!glob.is_prefix_match("docs/l"));
+        assert!(!glob.is_prefix_match("srx"));
+    }
+
+    #[test]
+    fn intersects_reports_overlapping_extension_patterns() {
+        let rs = Glob::new("*.rs").unwrap();
+        let src_rs = Glob::new("src/*.rs").unwrap();
+        assert!(rs.intersects(&src_rs));
+        assert!(src_rs.intersects(&rs));
+    }
+
+    #[test]
+    fn intersects_rejects_patterns_with_disjoint_required_extensions() {
+        let rs = Glob::new("*.rs").unwrap();
+        let md = Glob::new("*.md").unwrap();
+        assert!(!rs.intersects(&md));
+        assert!(!md.intersects(&rs));
+    }
+
+    #[test]
+    fn into_matcher_compiles_without_cloning_the_glob() {
+        let matcher = Glob::new("*.rs").unwrap().into_matcher();
+        assert!(matcher.is_match("main.rs"));
+        assert!(!matcher.is_match("main.txt"));
+    }
+
+    #[test]
+    fn compile_fast_matcher_agrees_with_compile_matcher() {
+        let glob = Glob::new("*.rs").unwrap();
+        let fast = glob.compile_fast_matcher();
+        assert!(fast.is_match("main.rs"));
+        assert!(!fast.is_match("main.txt"));
+    }
+
+    #[test]
+    fn is_match_entry_honors_dir_only_via_file_type() {
+        use std::fs::File;
+
+        let root = ::std::env::temp_dir()
+            .join("globset_is_match_entry_test");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("target")).unwrap();
+        File::create(root.join("target.rs")).unwrap();
+
+        let dirs_only = GlobBuilder::new("target/")
+            .directory_matching(true)
+            .basename_only(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        let mut saw_dir = false;
+        let mut saw_file = false;
+        for entry in fs::read_dir(&root).unwrap() {
+            let entry = entry.unwrap();
+            let matched = dirs_only.is_match_entry(&entry).unwrap();
+            if entry.file_name() == "target" {
+                assert!(matched);
+                saw_dir = true;
+            } else if entry.file_name() == "target.rs" {
+                assert!(!matched);
+                saw_file = true;
+            }
+        }
+        assert!(saw_dir && saw_file);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn separator_lets_wildcards_stop_at_a_custom_character() {
+        let glob = GlobBuilder::new("a.*.c")
+            .literal_separator(true)
+            .separator('.')
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("a.b.c"));
+        assert!(!glob.is_match("a.b.d.c"));
+        assert!(!glob.is_match("a/b/c"));
+    }
+
+    #[test]
+    fn separator_lets_globstar_span_custom_components() {
+        let glob = GlobBuilder::new("a.b.**")
+            .separator('.')
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("a.b"));
+        assert!(glob.is_match("a.b.c.d"));
+        assert!(!glob.is_match("a.b/c"));
+    }
+
+    #[test]
+    fn max_globstar_depth_bounds_how_many_components_a_globstar_consumes() {
+        let glob = GlobBuilder::new("foo/**/bar")
+            .max_globstar_depth(Some(2))
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo/a/bar"));
+        assert!(glob.is_match("foo/a/b/bar"));
+        assert!(!glob.is_match("foo/a/b/c/bar"));
+    }
+
+    #[test]
+    fn max_globstar_depth_bounds_a_trailing_globstar() {
+        let glob = GlobBuilder::new("foo/**")
+            .max_globstar_depth(Some(2))
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo"));
+        assert!(glob.is_match("foo/a/b"));
+        assert!(!glob.is_match("foo/a/b/c"));
+    }
+
+    #[test]
+    fn is_universal_recognizes_a_bare_globstar_and_globstar_star() {
+        assert!(Glob::new("**").unwrap().is_universal());
+        assert!(Glob::new("**/*").unwrap().is_universal());
+    }
+
+    #[test]
+    fn is_universal_rejects_a_glob_with_a_literal_component() {
+        assert!(!Glob::new("*.rs").unwrap().is_universal());
+    }
+
+    #[test]
+    fn is_universal_rejects_a_bounded_globstar() {
+        let glob = GlobBuilder::new("**")
+            .max_globstar_depth(Some(4))
+            .build()
+            .unwrap();
+        assert!(!glob.is_universal());
+    }
+
+    #[test]
+    fn required_extension_reports_the_extension_a_match_must_have() {
+        let glob = Glob::new("**/*.rs").unwrap();
+        assert_eq!(glob.required_extension(), Some(OsStr::new("rs")));
+
+        let glob = Glob::new("**/foo").unwrap();
+        assert_eq!(glob.required_extension(), None);
+    }
+
+    #[test]
+    fn wildcards_are_always_byte_oriented_not_unicode_aware() {
+        let glob = Glob::new("a?c").unwrap().compile_matcher();
+
+        // `?` consumes exactly one byte, so it can't stand in for a
+        // multi-byte UTF-8 character...
+        assert!(!glob.is_match("a\u{e9}c"));
+        // ...but it happily matches a single byte that isn't valid UTF-8
+        // on its own, since this crate never runs the regex engine in
+        // Unicode mode to begin with.
+        assert!(glob.is_match_bytes(b"a\xffc"));
+    }
+
+    #[test]
+    fn question_matches_bytes_toggles_one_byte_vs_one_scalar() {
+        // By default (`question_matches_bytes(true)`), a single `?` only
+        // ever consumes one byte, so a two-byte character needs two of them.
+        let byte_mode = GlobBuilder::new("a?c").build().unwrap().compile_matcher();
+        assert!(!byte_mode.is_match("a\u{e9}c"));
+        let byte_mode_two = GlobBuilder::new("a??c").build().unwrap().compile_matcher();
+        assert!(byte_mode_two.is_match("a\u{e9}c"));
+
+        // With `question_matches_bytes(false)`, a single `?` consumes one
+        // whole Unicode scalar value, however many bytes it's encoded as.
+        let scalar_mode = GlobBuilder::new("a?c")
+            .question_matches_bytes(false)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(scalar_mode.is_match("a\u{e9}c"));
+        assert!(!scalar_mode.is_match("a\u{e9}\u{e9}c"));
+    }
+
+    #[test]
+    fn star_stops_at_dot_keeps_star_from_crossing_the_extension_boundary() {
+        let glob = GlobBuilder::new("foo.*")
+            .star_stops_at_dot(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+
+        assert!(glob.is_match("foo.tar"));
+        assert!(!glob.is_match("foo.tar.gz"));
+
+        // Without the option, `*` matches `.` like any other character.
+        let default_glob = GlobBuilder::new("foo.*").build().unwrap().compile_matcher();
+        assert!(default_glob.is_match("foo.tar.gz"));
+    }
+
+    #[test]
+    fn star_stops_at_dot_composes_with_literal_separator() {
+        let glob = GlobBuilder::new("foo/*")
+            .star_stops_at_dot(true)
+            .literal_separator(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+
+        assert!(glob.is_match("foo/bar"));
+        assert!(!glob.is_match("foo/bar.txt"));
+        assert!(!glob.is_match("foo/bar/baz"));
+    }
+
+    #[test]
+    fn alternation_matches_members_containing_glob_metacharacters_literally() {
+        let glob = Glob::alternation(&["a,b", "c}d", "*.rs"])
+            .unwrap()
+            .compile_matcher();
+
+        assert!(glob.is_match("a,b"));
+        assert!(glob.is_match("c}d"));
+        assert!(glob.is_match("*.rs"));
+        // The literal `*` in the third member doesn't act as a wildcard.
+        assert!(!glob.is_match("main.rs"));
+        assert!(!glob.is_match("a"));
+    }
+
+    #[test]
+    fn trim_strips_leading_and_trailing_whitespace() {
+        let glob = GlobBuilder::new("  *.rs  ")
+            .trim(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("lib.rs"));
+        assert!(!glob.is_match(" lib.rs"));
+    }
+
+    #[test]
+    fn trim_leaves_an_escaped_trailing_space_intact() {
+        let glob = GlobBuilder::new("foo\\  ")
+            .trim(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo "));
+        assert!(!glob.is_match("foo"));
+    }
+
+    #[test]
+    fn ignore_trailing_comment_strips_a_hash_comment() {
+        let glob = GlobBuilder::new("*.rs   # rust sources")
+            .ignore_trailing_comment(true)
+            .trim(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("lib.rs"));
+    }
+
+    #[test]
+    fn ignore_trailing_comment_leaves_a_hash_inside_a_class_literal() {
+        let glob = GlobBuilder::new("foo[#]bar")
+            .ignore_trailing_comment(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo#bar"));
+    }
+
+    #[test]
+    fn ignore_trailing_comment_leaves_an_escaped_hash_literal() {
+        let glob = GlobBuilder::new("foo\\#bar")
+            .ignore_trailing_comment(true)
+            .build()
+            .unwrap()
+            .compile_matcher();
+        assert!(glob.is_match("foo#bar"));
+    }
+}
@@ -0,0 +1,112 @@
+use glob::Glob;
+use {Candidate, Error, GlobSet, GlobSetBuilder};
+
+/// GlobMap associates an arbitrary value with each glob in a `GlobSet`, so
+/// that a matched path can be routed straight to the value instead of a bare
+/// sequence number.
+///
+/// This is a thin wrapper: matching still goes through the same fast
+/// strategies a `GlobSet` uses, and the sequence number a match reports is
+/// simply used to index a parallel `Vec<V>`.
+#[derive(Clone, Debug)]
+pub struct GlobMap<V> {
+    set: GlobSet,
+    values: Vec<V>,
+}
+
+impl<V> GlobMap<V> {
+    /// Returns the number of glob/value pairs in this map.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if and only if this map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the values associated with every glob that matches the given
+    /// path, in the same order `GlobSet::matches` would report their
+    /// sequence numbers.
+    pub fn matches<P: AsRef<::std::path::Path>>(&self, path: P) -> Vec<&V> {
+        self.matches_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Returns the values associated with every glob that matches the given
+    /// candidate.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn matches_candidate(&self, path: &Candidate) -> Vec<&V> {
+        self.set
+            .matches_candidate(path)
+            .into_iter()
+            .map(|i| &self.values[i])
+            .collect()
+    }
+
+    /// Returns the value associated with the smallest-indexed glob that
+    /// matches the given path, or `None` if no glob matches.
+    pub fn first_match<P: AsRef<::std::path::Path>>(&self, path: P) -> Option<&V> {
+        self.first_match_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Returns the value associated with the smallest-indexed glob that
+    /// matches the given candidate, or `None` if no glob matches.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn first_match_candidate(&self, path: &Candidate) -> Option<&V> {
+        self.set.first_match_candidate(path).map(|i| &self.values[i])
+    }
+}
+
+/// GlobMapBuilder builds a `GlobMap`, associating a value with each glob
+/// added, mirroring `GlobSetBuilder`.
+pub struct GlobMapBuilder<V> {
+    builder: GlobSetBuilder,
+    values: Vec<V>,
+}
+
+impl<V> GlobMapBuilder<V> {
+    /// Create a new GlobMapBuilder. A GlobMapBuilder can be used to add new
+    /// glob/value pairs. Once all pairs have been added, `build` should be
+    /// called to produce a `GlobMap`, which can then be used for matching.
+    pub fn new() -> GlobMapBuilder<V> {
+        GlobMapBuilder { builder: GlobSetBuilder::new(), values: vec![] }
+    }
+
+    /// Add a new glob/value pair to this map.
+    pub fn add(&mut self, pat: Glob, value: V) -> &mut GlobMapBuilder<V> {
+        self.builder.add(pat);
+        self.values.push(value);
+        self
+    }
+
+    /// Builds a new map from all of the glob/value pairs added so far.
+    ///
+    /// Once a map is built, no new pairs can be added to it.
+    pub fn build(self) -> Result<GlobMap<V>, Error> {
+        let set = try!(self.builder.build());
+        Ok(GlobMap { set: set, values: self.values })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glob::Glob;
+    use super::GlobMapBuilder;
+
+    #[test]
+    fn routes_matches_to_their_associated_value() {
+        let mut builder = GlobMapBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap(), "rust");
+        builder.add(Glob::new("*.md").unwrap(), "markdown");
+        let map = builder.build().unwrap();
+
+        assert_eq!(map.first_match("lib.rs"), Some(&"rust"));
+        assert_eq!(map.first_match("README.md"), Some(&"markdown"));
+        assert_eq!(map.first_match("foo.c"), None);
+        assert_eq!(map.matches("lib.rs"), vec![&"rust"]);
+    }
+}
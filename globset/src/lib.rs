@@ -87,8 +87,18 @@ Standard Unix-style glob syntax is supported:
   else is illegal (N.B. the glob `**` is allowed and means "match everything").
 * `{a,b}` matches `a` or `b` where `a` and `b` are arbitrary glob patterns.
   (N.B. Nesting `{...}` is not currently allowed.)
+* `{start..end}` matches any integer in the inclusive range `start` to `end`,
+  e.g. `{1..3}` matches `1`, `2` or `3`. Both bounds may be negative, and
+  `start` may be greater than `end` to count down instead of up. If either
+  bound is written with leading zeros, e.g. `{01..12}`, every member is
+  zero-padded to that width. `{start..end}` also accepts a single
+  lowercase or uppercase ASCII letter on each side, e.g. `{a..e}` matches
+  `a` through `e`.
 * `[ab]` matches `a` or `b` where `a` and `b` are characters. Use
-  `[!ab]` to match any character except for `a` and `b`.
+  `[!ab]` to match any character except for `a` and `b`. A character class
+  may also contain a POSIX named class, e.g. `[[:alpha:]_]` matches an
+  alphabetic character or an underscore, and may be mixed freely with
+  literal characters and ranges.
 * Metacharacters such as `*` and `?` can be escaped with character class
   notation. e.g., `[*]` matches `*`.
 
@@ -104,40 +114,143 @@ extern crate fnv;
 extern crate log;
 extern crate memchr;
 extern crate regex;
+#[cfg(feature = "serde1")]
+extern crate serde;
+#[cfg(feature = "serde1")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(all(test, feature = "serde1"))]
+extern crate serde_json;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "smallvec")]
+extern crate smallvec;
 
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
 use std::error::Error as StdError;
 use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::hash;
+use std::io::{self, BufRead, Write};
 use std::path::Path;
+use std::rc::Rc;
 use std::str;
 
 use aho_corasick::{Automaton, AcAutomaton, FullAcAutomaton};
-use regex::bytes::{Regex, RegexBuilder, RegexSet};
+use regex::bytes::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "smallvec")]
+use smallvec::SmallVec;
 
 use pathutil::{
-    file_name, file_name_ext, normalize_path, os_str_bytes, path_bytes,
+    file_name, file_name_bytes, file_name_ext, file_name_ext_bytes,
+    file_name_stem, file_stem_bytes, normalize_path, os_str_bytes, path_bytes,
 };
 use glob::MatchStrategy;
-pub use glob::{Glob, GlobBuilder, GlobMatcher};
+pub use glob::{validate, EmptyMode, Glob, GlobBuilder, GlobCache, GlobMatcher};
+pub use glob_map::{GlobMap, GlobMapBuilder};
+pub use pathset::PathSet;
 
 mod glob;
+mod glob_map;
+mod pathset;
 mod pathutil;
 
 /// Represents an error that can occur when parsing a glob pattern.
+///
+/// This records both the underlying kind of error and, when available, the
+/// glob pattern that produced it, so that a caller building a large set of
+/// patterns (e.g. a `GlobSetBuilder`) can report which of many patterns was
+/// at fault.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Error {
+    glob: Option<String>,
+    kind: ErrorKind,
+    pos: Option<usize>,
+    glob_index: Option<usize>,
+}
+
+impl Error {
+    /// Return the glob pattern associated with this error, if one exists.
+    pub fn glob(&self) -> Option<&str> {
+        self.glob.as_ref().map(|s| &**s)
+    }
+
+    /// Return the kind of this error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Return the byte offset into the glob pattern where this error
+    /// occurred, if it's known.
+    ///
+    /// Not every error variant can be pinned to a precise location, so this
+    /// is `None` unless the parser was able to identify one, e.g. the byte
+    /// offset of the `[` that starts an unclosed character class.
+    pub fn pos(&self) -> Option<usize> {
+        self.pos
+    }
+
+    /// Return the zero-based index of the pattern that caused this error,
+    /// when it originated from `GlobSetBuilder::build`.
+    ///
+    /// This is `None` unless the error came from building a `GlobSet` out
+    /// of several patterns, in which case it's the same sequence number
+    /// `GlobSet::matches` would have reported for that pattern had it
+    /// compiled successfully.
+    pub fn glob_index(&self) -> Option<usize> {
+        self.glob_index
+    }
+
+    fn from_kind(kind: ErrorKind) -> Error {
+        Error { glob: None, kind: kind, pos: None, glob_index: None }
+    }
+
+    /// Attaches a glob pattern to this error if one isn't already set.
+    fn with_glob(mut self, glob: &str) -> Error {
+        if self.glob.is_none() {
+            self.glob = Some(glob.to_string());
+        }
+        self
+    }
+
+    /// Attaches a byte offset to this error if one isn't already set.
+    fn with_pos(mut self, pos: usize) -> Error {
+        if self.pos.is_none() {
+            self.pos = Some(pos);
+        }
+        self
+    }
+
+    /// Attaches a pattern's sequence number to this error if one isn't
+    /// already set.
+    fn with_glob_index(mut self, index: usize) -> Error {
+        if self.glob_index.is_none() {
+            self.glob_index = Some(index);
+        }
+        self
+    }
+}
+
+/// The kind of error that can occur when parsing a glob pattern.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub enum Error {
+pub enum ErrorKind {
     /// Occurs when a use of `**` is invalid. Namely, `**` can only appear
     /// adjacent to a path separator, or the beginning/end of a glob.
     InvalidRecursive,
     /// Occurs when a character class (e.g., `[abc]`) is not closed.
     UnclosedClass,
-    /// Occurs when a range in a character (e.g., `[a-z]`) is invalid. For
-    /// example, if the range starts with a lexicographically larger character
-    /// than it ends with.
+    /// Occurs when a range in a character class (e.g., `[a-z]`) or a
+    /// letter brace range (e.g., `{a..z}`) is invalid. For example, if the
+    /// range starts with a lexicographically larger character than it ends
+    /// with.
     InvalidRange(char, char),
+    /// Occurs when a `[:name:]` POSIX named class inside a character class
+    /// uses a `name` that isn't recognized, e.g. `[[:bogus:]]`.
+    UnrecognizedPosixClass(String),
     /// Occurs when a `}` is found without a matching `{`.
     UnopenedAlternates,
     /// Occurs when a `{` is found without a matching `}`.
@@ -145,81 +258,359 @@ pub enum Error {
     /// Occurs when an alternating group is nested inside another alternating
     /// group, e.g., `{{a,b},{c,d}}`.
     NestedAlternates,
+    /// Occurs when a `?(...)`, `*(...)`, `+(...)` or `@(...)` extglob group
+    /// is not closed with a matching `)`.
+    UnclosedExtGlob,
+    /// Occurs when `!(...)` extglob negation is used. This crate's regex
+    /// engine has no lookaround, so there's no way to compile a complement
+    /// pattern for it.
+    UnsupportedExtGlobNegation,
+    /// Occurs when a `{start..end}` numeric brace range would expand to
+    /// more alternates than is reasonable to build a regex from.
+    RangeTooLarge,
+    /// Occurs when the `step` in a `{start..end..step}` numeric or letter
+    /// brace range is zero or has the wrong sign to ever reach `end` from
+    /// `start`, e.g. `{0..10..0}` or `{0..10..-2}`.
+    InvalidRangeStep(i64),
     /// An error associated with parsing or compiling a regex.
     Regex(String),
+    /// Occurs when a glob pattern is given as an `OsStr` that isn't valid
+    /// UTF-8. This crate's glob parser works on `&str`, so patterns must be
+    /// UTF-8 encoded.
+    InvalidUtf8,
+    /// Occurs when a `\xHH` or `\OOO` backslash escape (only recognized
+    /// when `GlobBuilder::backslash_escape` is enabled) isn't followed by
+    /// the right number of hex/octal digits, or its value doesn't fit in
+    /// a byte.
+    InvalidEscape(String),
+    /// Occurs when a pattern is empty and `GlobBuilder::empty_matches` was
+    /// set to `EmptyMode::Error`.
+    EmptyGlob,
+    /// Occurs when `Glob::new_with_vars` sees a `$VAR`/`${VAR}` placeholder
+    /// whose name isn't a key in the `vars` map it was given.
+    UndefinedVar(String),
 }
 
-impl StdError for Error {
+impl ErrorKind {
     fn description(&self) -> &str {
         match *self {
-            Error::InvalidRecursive => {
+            ErrorKind::InvalidRecursive => {
                 "invalid use of **; must be one path component"
             }
-            Error::UnclosedClass => {
+            ErrorKind::UnclosedClass => {
                 "unclosed character class; missing ']'"
             }
-            Error::InvalidRange(_, _) => {
+            ErrorKind::InvalidRange(_, _) => {
                 "invalid character range"
             }
-            Error::UnopenedAlternates => {
+            ErrorKind::UnrecognizedPosixClass(_) => {
+                "unrecognized POSIX named class"
+            }
+            ErrorKind::UnopenedAlternates => {
                 "unopened alternate group; missing '{' \
                 (maybe escape '}' with '[}]'?)"
             }
-            Error::UnclosedAlternates => {
+            ErrorKind::UnclosedAlternates => {
                 "unclosed alternate group; missing '}' \
                 (maybe escape '{' with '[{]'?)"
             }
-            Error::NestedAlternates => {
+            ErrorKind::NestedAlternates => {
                 "nested alternate groups are not allowed"
             }
-            Error::Regex(ref err) => err,
+            ErrorKind::UnclosedExtGlob => {
+                "unclosed extglob group; missing ')'"
+            }
+            ErrorKind::UnsupportedExtGlobNegation => {
+                "!(...) extglob negation is not supported"
+            }
+            ErrorKind::RangeTooLarge => {
+                "numeric brace range is too large to expand"
+            }
+            ErrorKind::InvalidRangeStep(_) => {
+                "brace range step must be a positive integer"
+            }
+            ErrorKind::Regex(ref err) => err,
+            ErrorKind::InvalidUtf8 => "glob patterns must be valid UTF-8",
+            ErrorKind::InvalidEscape(_) => "invalid backslash escape sequence",
+            ErrorKind::EmptyGlob => "empty glob patterns are not allowed",
+            ErrorKind::UndefinedVar(_) => "undefined variable in pattern",
         }
     }
 }
 
+impl StdError for Error {
+    fn description(&self) -> &str {
+        self.kind.description()
+    }
+}
+
 impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.glob {
+            Some(ref glob) => {
+                write!(f, "error parsing glob '{}': {}", glob, self.kind)
+            }
+            None => self.kind.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Error::InvalidRecursive
-            | Error::UnclosedClass
-            | Error::UnopenedAlternates
-            | Error::UnclosedAlternates
-            | Error::NestedAlternates
-            | Error::Regex(_) => {
+            ErrorKind::InvalidRecursive
+            | ErrorKind::UnclosedClass
+            | ErrorKind::UnopenedAlternates
+            | ErrorKind::UnclosedAlternates
+            | ErrorKind::NestedAlternates
+            | ErrorKind::UnclosedExtGlob
+            | ErrorKind::UnsupportedExtGlobNegation
+            | ErrorKind::RangeTooLarge
+            | ErrorKind::Regex(_)
+            | ErrorKind::InvalidUtf8
+            | ErrorKind::EmptyGlob => {
                 write!(f, "{}", self.description())
             }
-            Error::InvalidRange(s, e) => {
+            ErrorKind::UnrecognizedPosixClass(ref name) => {
+                write!(f, "unrecognized POSIX named class '[:{}:]'", name)
+            }
+            ErrorKind::InvalidRange(s, e) => {
                 write!(f, "invalid range; '{}' > '{}'", s, e)
             }
+            ErrorKind::InvalidRangeStep(step) => {
+                write!(f, "invalid range step '{}'; must be positive", step)
+            }
+            ErrorKind::InvalidEscape(ref esc) => {
+                write!(f, "invalid backslash escape sequence '{}'", esc)
+            }
+            ErrorKind::UndefinedVar(ref name) => {
+                write!(f, "undefined variable '{}'", name)
+            }
         }
     }
 }
 
-fn new_regex(pat: &str) -> Result<Regex, Error> {
+/// The size limit, in bytes, applied to a compiled regex's program and its
+/// lazy DFA cache when neither `GlobBuilder::regex_size_limit`/
+/// `dfa_size_limit` nor their `GlobSetBuilder` equivalents have been used to
+/// override it.
+const DEFAULT_SIZE_LIMIT: usize = 10 * (1 << 20);
+
+/// The number of matches `GlobSet::matches_smallvec` can hold inline
+/// before it spills onto the heap. Most paths match zero or one glob, so
+/// this is sized generously above that common case.
+#[cfg(feature = "smallvec")]
+pub const MATCHES_SMALLVEC_INLINE_SIZE: usize = 4;
+
+fn new_regex(
+    pat: &str,
+    size_limit: usize,
+    dfa_size_limit: usize,
+) -> Result<Regex, Error> {
     RegexBuilder::new(pat)
         .dot_matches_new_line(true)
-        .size_limit(10 * (1 << 20))
-        .dfa_size_limit(10 * (1 << 20))
+        .size_limit(size_limit)
+        .dfa_size_limit(dfa_size_limit)
         .build()
-        .map_err(|err| Error::Regex(err.to_string()))
+        .map_err(|err| Error::from_kind(ErrorKind::Regex(err.to_string())))
 }
 
-fn new_regex_set<I, S>(pats: I) -> Result<RegexSet, Error>
+fn new_regex_set<I, S>(
+    pats: I,
+    size_limit: usize,
+    dfa_size_limit: usize,
+) -> Result<RegexSet, Error>
         where S: AsRef<str>, I: IntoIterator<Item=S> {
-    RegexSet::new(pats).map_err(|err| Error::Regex(err.to_string()))
+    RegexSetBuilder::new(pats)
+        .size_limit(size_limit)
+        .dfa_size_limit(dfa_size_limit)
+        .build()
+        .map_err(|err| Error::from_kind(ErrorKind::Regex(err.to_string())))
+}
+
+/// Extracts a literal byte string that must appear verbatim in any string
+/// matched by the compiled regex source `pat`, if one can be found cheaply.
+///
+/// This is a conservative heuristic, not a real regex parse: it walks `pat`
+/// tracking parenthesis depth and only considers runs of plain, unescaped
+/// characters seen at depth zero (so nothing inside a group, which could be
+/// made optional or alternated away, is ever used). A run is also cut short
+/// before any character immediately followed by a `*`, `+`, `?`, or `{`
+/// quantifier, since a quantifier can make part of a run vanish. Character
+/// classes (`[...]`) are skipped entirely rather than risked. The longest
+/// surviving run is returned if it's long enough to be worth an extra
+/// Aho-Corasick pass; `None` otherwise, in which case the caller must fall
+/// back to running the regex directly. Used only to build an internal
+/// "does this candidate even contain what's required" pre-filter in front
+/// of `RegexSetStrategy`; getting this wrong would mean rejecting a real
+/// match, so it only ever returns substrings it's sure are required.
+fn required_literal(pat: &str) -> Option<String> {
+    const MIN_LEN: usize = 3;
+
+    fn flush(current: &mut String, best: &mut String) {
+        if current.len() > best.len() {
+            *best = current.clone();
+        }
+        current.clear();
+    }
+
+    let bytes = pat.as_bytes();
+    let mut depth: i32 = 0;
+    let mut current = String::new();
+    let mut best = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] as char {
+            '(' => { depth += 1; flush(&mut current, &mut best); i += 1; }
+            ')' => { depth -= 1; flush(&mut current, &mut best); i += 1; }
+            '[' => {
+                flush(&mut current, &mut best);
+                while i < bytes.len() && bytes[i] as char != ']' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '\\' if i + 1 < bytes.len() => {
+                if depth == 0 {
+                    current.push(bytes[i + 1] as char);
+                }
+                i += 2;
+            }
+            '*' | '+' | '?' | '{' => {
+                // The quantifier applies to whatever came right before it,
+                // so that character isn't actually required.
+                current.pop();
+                flush(&mut current, &mut best);
+                i += 1;
+            }
+            '.' | '^' | '$' | '|' | '}' => {
+                flush(&mut current, &mut best);
+                i += 1;
+            }
+            c if depth == 0 => { current.push(c); i += 1; }
+            _ => { i += 1; }
+        }
+    }
+    flush(&mut current, &mut best);
+    if best.len() >= MIN_LEN { Some(best) } else { None }
+}
+
+/// Whether `xs` is already sorted in (non-strict) ascending order.
+fn is_sorted_ascending(xs: &[usize]) -> bool {
+    xs.windows(2).all(|w| w[0] <= w[1])
 }
 
 type Fnv = hash::BuildHasherDefault<fnv::FnvHasher>;
 
+/// The result of resolving a path against an ordered, possibly-negated set
+/// of patterns, such as a gitignore or Mercurial `hgignore` rule file.
+///
+/// Unlike `GlobSet::matches`, which reports every matching pattern, `Match`
+/// reports only the final decision: the last (highest insertion index)
+/// matching pattern wins, and whether that pattern was negated determines
+/// whether the path is excluded or included.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Match {
+    /// No pattern in the set matched this path.
+    None,
+    /// The last matching pattern was negated, so this path should be
+    /// included, overriding an earlier ordinary pattern that excluded it.
+    Whitelist,
+    /// The last matching pattern was an ordinary (non-negated) pattern, so
+    /// this path should be excluded.
+    Ignore,
+}
+
+/// Describes how a pattern's sequence number changed between two versions
+/// of a `GlobSet` built by `GlobSetBuilder::build_diff`.
+///
+/// A pattern is identified across the two sets by its original text (and,
+/// for a glob, its negated flag), not by index, so inserting or removing a
+/// pattern in the middle of a long list reports one `Added`/`Removed` pair
+/// rather than a `Moved` for every pattern after it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IndexChange {
+    /// A pattern present in the new set but not the previous one, at the
+    /// given new sequence number.
+    Added(usize),
+    /// A pattern present in the previous set but not the new one, at the
+    /// given previous sequence number.
+    Removed(usize),
+    /// A pattern present in both sets but at a different sequence number:
+    /// `from` in the previous set, `to` in the new one.
+    Moved {
+        /// The pattern's sequence number in the previous set.
+        from: usize,
+        /// The pattern's sequence number in the new set.
+        to: usize,
+    },
+}
+
+/// Reports that a pattern added to a `GlobSetBuilder` was dropped by
+/// `build_deduped` because it was identical to one already added.
+///
+/// Both fields are sequence numbers in the builder's original, pre-dedup
+/// insertion order, not in the resulting `GlobSet`, since the dropped
+/// pattern by definition has no sequence number of its own in that set.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Duplicate {
+    /// The sequence number of the pattern that was kept.
+    pub original: usize,
+    /// The sequence number of the pattern that was dropped because it
+    /// matched `original`'s text and negated flag exactly.
+    pub duplicate: usize,
+}
+
 /// GlobSet represents a group of globs that can be matched together in a
 /// single pass.
 #[derive(Clone, Debug)]
 pub struct GlobSet {
     len: usize,
     strats: Vec<GlobSetMatchStrategy>,
+    negated: Vec<bool>,
+    enabled: Vec<bool>,
+    pats: Vec<Pattern>,
+    globs: Vec<Glob>,
+    // Which strategy each pattern in `pats`, by sequence number, was
+    // classified into at build time. Unlike `explain`, which reports the
+    // strategy that actually produced a match against a given path, this
+    // is a static classification available without any path at all.
+    strategies: Vec<MatchSource>,
+    // The caller-supplied tag for each pattern, if any, set via
+    // `GlobSetBuilder::add_tagged`.
+    tags: Vec<Option<String>>,
 }
 
 impl GlobSet {
+    /// Returns a `GlobSet` with no patterns, which never matches anything.
+    ///
+    /// This is a cheaper way to get an empty set than
+    /// `GlobSetBuilder::new().build().unwrap()`, and is handy as a default
+    /// value in structs that hold a `GlobSet`.
+    pub fn empty() -> GlobSet {
+        GlobSet {
+            len: 0,
+            strats: vec![],
+            negated: vec![],
+            enabled: vec![],
+            pats: vec![],
+            globs: vec![],
+            strategies: vec![],
+            tags: vec![],
+        }
+    }
+
+    /// Builds a `GlobSet` directly from an iterator of `Glob`s, equivalent
+    /// to feeding each one to a fresh `GlobSetBuilder` and calling `build`.
+    ///
+    /// A plain `impl FromIterator<Glob> for GlobSet` isn't offered instead,
+    /// since building a set (unlike adding a single glob to a builder) can
+    /// fail, e.g. by exceeding `GlobSetBuilder::regex_size_limit`, and
+    /// `FromIterator::from_iter` has no way to report that.
+    pub fn from_iter<T: IntoIterator<Item = Glob>>(iter: T) -> Result<GlobSet, Error> {
+        iter.into_iter().collect::<GlobSetBuilder>().build()
+    }
+
     /// Returns true if this set is empty, and therefore matches nothing.
     pub fn is_empty(&self) -> bool {
         self.len == 0
@@ -230,21 +621,256 @@ impl GlobSet {
         self.len
     }
 
+    /// Enables or disables the glob at `index` without rebuilding the set.
+    ///
+    /// A disabled glob's automaton still runs (there's no cheap way to skip
+    /// it mid-strategy), but it's filtered out of every match result, as if
+    /// it had never been added. This is meant for a live-reload scenario
+    /// where rules are toggled on and off far more often than the full set
+    /// of patterns changes. All globs start out enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.len()`.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        self.enabled[index] = enabled;
+    }
+
+    /// Returns whether the glob at `index` is currently enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.len()`.
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.enabled[index]
+    }
+
+    /// Whether every glob in this set is currently enabled, which is the
+    /// common case and lets matching skip the per-index enabled check.
+    fn all_enabled(&self) -> bool {
+        self.enabled.iter().all(|&e| e)
+    }
+
+    /// Returns the original `Glob`s added to this set, in insertion order,
+    /// so that a sequence number returned by `matches` or `first_match` can
+    /// be mapped back to the pattern that produced it, e.g. for error
+    /// messages like "no files matched pattern X".
+    ///
+    /// Patterns added via `GlobSetBuilder::add_regex` (or its negated
+    /// variant) have no `Glob` representation and are omitted here, so this
+    /// slice is only aligned with sequence numbers for a set built entirely
+    /// from `add`/`add_negated`.
+    pub fn globs(&self) -> &[Glob] {
+        &self.globs
+    }
+
+    /// Returns true if any glob in this set could possibly match some path
+    /// nested under `dir`, using only each glob's `Glob::literal_prefix()`.
+    ///
+    /// This is meant for pruning a directory walk (e.g. with `walkdir`)
+    /// before it descends into a subtree that provably can't contain a
+    /// match, without running the full matcher against every file it would
+    /// otherwise visit. A glob with no literal prefix at all (e.g.
+    /// `**/foo`) has nothing to prune with, so it conservatively reports a
+    /// possible match; a raw regex added via `add_regex` is treated the
+    /// same way, for the same reason.
+    ///
+    /// Two paths are ruled out as unrelated only when they diverge before
+    /// either one ends *and* the point where they diverge isn't a path
+    /// separator in the longer one — so a directory named `src` isn't
+    /// confused with an unrelated prefix like `srcfoo/`. When `dir` is at
+    /// least as long as the literal prefix, only a plain byte-string
+    /// prefix check is done, since anything beyond the prefix is exactly
+    /// what the glob's own wildcard would still need to account for.
+    pub fn could_match_under(&self, dir: &Path) -> bool {
+        let candidate = Candidate::new(dir);
+        let dir_path: &[u8] = &candidate.path;
+        for p in &self.pats {
+            let prefix = match *p {
+                Pattern::Glob(ref g) => g.literal_prefix(),
+                Pattern::Regex(_) => return true,
+            };
+            if prefix.is_empty() {
+                return true;
+            }
+            let prefix = prefix.as_bytes();
+            let related = if dir_path.len() >= prefix.len() {
+                dir_path.starts_with(prefix)
+            } else {
+                prefix.starts_with(dir_path)
+                    && (dir_path.is_empty()
+                        || dir_path[dir_path.len() - 1] == b'/'
+                        || prefix[dir_path.len()] == b'/')
+            };
+            if related {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Merges this set with `other`, producing a new set that matches
+    /// anything either one would, without re-parsing any of the original
+    /// patterns.
+    ///
+    /// The patterns from `other` are reindexed after this set's, offset by
+    /// `self.len()`: a merged sequence number `i` came from `self` if
+    /// `i < self.len()`, or from `other` at its original index
+    /// `i - self.len()` otherwise. Per-glob enabled state (see
+    /// `set_enabled`) is not preserved by the merge; every pattern in the
+    /// result starts out enabled.
+    pub fn merge(&self, other: &GlobSet) -> Result<GlobSet, Error> {
+        let mut pats = self.pats.clone();
+        pats.extend(other.pats.iter().cloned());
+        let mut negated = self.negated.clone();
+        negated.extend(other.negated.iter().cloned());
+        let mut tags = self.tags.clone();
+        tags.extend(other.tags.iter().cloned());
+        GlobSet::new(
+            &pats, &negated, &tags, DEFAULT_SIZE_LIMIT, DEFAULT_SIZE_LIMIT)
+    }
+
+    /// Builds a `GlobSet` directly from the lines of a gitignore-style file.
+    ///
+    /// This is shorthand for `parse_patterns(lines).and_then(|b| b.build())`
+    /// for callers who just want a matcher and don't need the intermediate
+    /// `GlobSetBuilder`. Comments (`#`) and blank lines are skipped, and a
+    /// leading `!` negates a line the same way `GlobSetBuilder::add_negated`
+    /// does: the pattern still participates in ordinary matching, but if
+    /// it's the last one to match a path, `GlobSet::matched` reports
+    /// `Match::Whitelist` instead of `Match::Ignore`, letting a later line
+    /// re-include a path an earlier one excluded. See `parse_patterns` for
+    /// the full set of line prefixes this understands (e.g. `glob:`, `re:`)
+    /// beyond plain gitignore syntax.
+    pub fn from_gitignore_lines<I, S>(lines: I) -> Result<GlobSet, Error>
+            where S: AsRef<str>, I: IntoIterator<Item=S> {
+        try!(parse_patterns(lines)).build()
+    }
+
+    /// Reads newline-separated paths from `r` and writes the ones this set
+    /// matches back to `w`, one per line, e.g. for filtering the output of
+    /// `find` piped into a tool built on this crate.
+    ///
+    /// Paths are read as raw bytes via `Candidate::from_bytes` rather than
+    /// `BufRead::lines`, so a non-UTF-8 path doesn't abort the whole
+    /// stream, and both `\n` and `\r\n` line endings are accepted (a
+    /// trailing `\r` is stripped before matching). Every written line ends
+    /// in a plain `\n`, regardless of the input's line ending. A single
+    /// buffer is reused across every line to amortize allocation, the same
+    /// way `Candidate` reuse does for in-process matching loops.
+    pub fn filter_reader<R: BufRead, W: Write>(
+        &self,
+        mut r: R,
+        mut w: W,
+    ) -> io::Result<()> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let n = try!(r.read_until(b'\n', &mut line));
+            if n == 0 {
+                break;
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+            }
+            if self.is_match_candidate(&Candidate::from_bytes(&line)) {
+                try!(w.write_all(&line));
+                try!(w.write_all(b"\n"));
+            }
+        }
+        Ok(())
+    }
+
     /// Returns true if any glob in this set matches the path given.
     pub fn is_match<P: AsRef<Path>>(&self, path: P) -> bool {
         self.is_match_candidate(&Candidate::new(path.as_ref()))
     }
 
+    /// Returns true if any glob in this set matches `path`.
+    ///
+    /// Unlike `is_match`, `path` is assumed to already be normalized: using
+    /// `/` as its only separator, with no trailing separator. This skips
+    /// the `normalize_path` scan `is_match` always runs, which saves work
+    /// in a hot loop when the caller can guarantee its paths are already in
+    /// that form. See `Candidate::new_normalized` for what happens if that
+    /// assumption doesn't hold.
+    pub fn is_match_str(&self, path: &str) -> bool {
+        self.is_match_candidate(&Candidate::new_normalized(path))
+    }
+
+    /// Returns the last-match-wins decision for the given path.
+    ///
+    /// This walks every pattern that matches `path` (in insertion order)
+    /// and returns the decision dictated by whichever one has the highest
+    /// insertion index: `Match::Whitelist` if that pattern was added via
+    /// `GlobSetBuilder::add_negated`, or `Match::Ignore` otherwise. If no
+    /// pattern matches, `Match::None` is returned.
+    pub fn matched<P: AsRef<Path>>(&self, path: P) -> Match {
+        self.matched_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Returns the last-match-wins decision for the given path.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn matched_candidate(&self, path: &Candidate) -> Match {
+        if self.is_empty() {
+            return Match::None;
+        }
+        let mut matches = vec![];
+        self.matches_candidate_into(path, &mut matches);
+        match matches.last() {
+            None => Match::None,
+            Some(&i) => {
+                if self.negated[i] {
+                    Match::Whitelist
+                } else {
+                    Match::Ignore
+                }
+            }
+        }
+    }
+
     /// Returns true if any glob in this set matches the path given.
     ///
     /// This takes a Candidate as input, which can be used to amortize the
     /// cost of preparing a path for matching.
+    ///
+    /// Every strategy's own `is_match` is written to answer "does anything
+    /// match" without doing the extra work `matches_into` needs to report
+    /// *which* patterns matched: `RegexSetStrategy` calls the underlying
+    /// `RegexSet::is_match` (which stops at the first match internally)
+    /// rather than `RegexSet::matches` (which runs every pattern in the
+    /// set to build a complete bitset), and `PrefixStrategy`/
+    /// `SuffixStrategy` return out of their `find_overlapping` loop as
+    /// soon as a hit lands at the required end of the candidate, rather
+    /// than collecting every overlapping hit first. So `is_match_candidate`
+    /// itself only needs to stop at the first strategy that reports a hit,
+    /// which the `all_enabled` fast path below does directly; the slower
+    /// path (only taken when some pattern has been disabled via
+    /// `set_enabled`) still short-circuits per strategy, since a disabled
+    /// pattern can only be discovered by first knowing which pattern
+    /// matched.
     pub fn is_match_candidate(&self, path: &Candidate) -> bool {
         if self.is_empty() {
             return false;
         }
+        if self.all_enabled() {
+            for strat in &self.strats {
+                if strat.is_match(path) {
+                    return true;
+                }
+            }
+            return false;
+        }
+        let mut buf = vec![];
         for strat in &self.strats {
-            if strat.is_match(path) {
+            buf.clear();
+            strat.matches_into(path, &mut buf);
+            if buf.iter().any(|&i| self.enabled[i]) {
                 return true;
             }
         }
@@ -252,11 +878,157 @@ impl GlobSet {
     }
 
     /// Returns the sequence number of every glob pattern that matches the
-    /// given path.
+    /// given path, in ascending order.
+    ///
+    /// This ordering is a guaranteed part of the API, not an incidental
+    /// side effect of how matching happens to be implemented internally;
+    /// code that relies on it (e.g. picking the last match to implement
+    /// gitignore-style override semantics, as `GlobSet::matched` does) can
+    /// depend on it continuing to hold.
     pub fn matches<P: AsRef<Path>>(&self, path: P) -> Vec<usize> {
         self.matches_candidate(&Candidate::new(path.as_ref()))
     }
 
+    /// An alias for `matches`, for callers who want the ascending-order
+    /// guarantee spelled out at the call site rather than only in `matches`'
+    /// doc comment.
+    pub fn matches_in_insertion_order<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Vec<usize> {
+        self.matches(path)
+    }
+
+    /// Returns the sequence number of every glob pattern that matches
+    /// `path`.
+    ///
+    /// Like `is_match_str`, `path` is assumed to already be normalized; see
+    /// `Candidate::new_normalized`.
+    pub fn matches_str(&self, path: &str) -> Vec<usize> {
+        self.matches_candidate(&Candidate::new_normalized(path))
+    }
+
+    /// Returns the sequence number of every glob pattern that matches
+    /// `path` once `base` has been stripped from its front, or an empty
+    /// `Vec` if `path` isn't under `base`.
+    ///
+    /// This centralizes the common pattern of matching a set of relative
+    /// globs against absolute paths that all live under a known project
+    /// root, so callers don't have to strip the prefix (and get the
+    /// allocation/off-by-one details right) themselves.
+    pub fn matches_relative<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        base: P,
+        path: Q,
+    ) -> Vec<usize> {
+        match path.as_ref().strip_prefix(base.as_ref()) {
+            Ok(rel) => self.matches(rel),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Returns the sequence number of every glob pattern that matches
+    /// `path` once its first `strip_components` path components have been
+    /// dropped, or an empty `Vec` if `path` has fewer components than that.
+    ///
+    /// This is `matches_relative` for callers who want to discard a
+    /// volatile prefix, e.g. a timestamped build directory, by component
+    /// count rather than by an exact known base path.
+    pub fn matches_stripping<P: AsRef<Path>>(
+        &self,
+        strip_components: usize,
+        path: P,
+    ) -> Vec<usize> {
+        let path = path.as_ref();
+        let mut components = path.components();
+        for _ in 0..strip_components {
+            if components.next().is_none() {
+                return vec![];
+            }
+        }
+        self.matches(components.as_path())
+    }
+
+    /// Returns the sequence number of every glob pattern that matches
+    /// `path` once it's been resolved relative to the process's current
+    /// working directory, as reported by `std::env::current_dir`.
+    ///
+    /// This centralizes the CLI case of matching working-directory-relative
+    /// patterns like `src/*.rs` against a path the user gave as either
+    /// relative or absolute, so callers don't have to fetch and strip the
+    /// current directory themselves. If the current directory can't be
+    /// determined, or `path` doesn't live under it, this returns an empty
+    /// `Vec`, the same as `matches_relative` failing to strip its base.
+    pub fn matches_cwd<P: AsRef<Path>>(&self, path: P) -> Vec<usize> {
+        let cwd = match env::current_dir() {
+            Ok(cwd) => cwd,
+            Err(_) => return vec![],
+        };
+        let path = path.as_ref();
+        if path.is_absolute() {
+            self.matches_relative(&cwd, path)
+        } else {
+            self.matches_relative(&cwd, cwd.join(path))
+        }
+    }
+
+    /// Returns the smallest sequence number of any glob pattern that
+    /// matches the given path, or `None` if no glob matches.
+    ///
+    /// Unlike `matches`, this never allocates a `Vec` or sorts anything: it
+    /// probes every strategy for a match and keeps the smallest index seen,
+    /// short-circuiting as soon as index `0` turns up (since no smaller
+    /// index can exist). In the worst case (no match, or only the
+    /// highest-numbered pattern matches) it still visits every strategy.
+    pub fn first_match<P: AsRef<Path>>(&self, path: P) -> Option<usize> {
+        self.first_match_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Returns the smallest sequence number of any glob pattern that
+    /// matches the given path, or `None` if no glob matches.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn first_match_candidate(&self, path: &Candidate) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut best: Option<usize> = None;
+        let mut buf = vec![];
+        for strat in &self.strats {
+            buf.clear();
+            strat.matches_into(path, &mut buf);
+            for &i in &buf {
+                if !self.enabled[i] {
+                    continue;
+                }
+                if i == 0 {
+                    return Some(0);
+                }
+                best = Some(match best {
+                    None => i,
+                    Some(cur) => ::std::cmp::min(cur, i),
+                });
+            }
+        }
+        best
+    }
+
+    /// Returns the sequence number of the highest-priority (i.e. earliest
+    /// added) glob pattern that matches the given path, or `None` if no
+    /// glob matches.
+    ///
+    /// This is `first_match` under a name that says what it's for in a
+    /// router built on "first pattern added wins" semantics: it's the same
+    /// algorithm, with the same short-circuit on index `0` and the same
+    /// worst-case complexity of visiting every strategy once (`O(number of
+    /// strategies)`, not `O(number of patterns)`), because it never
+    /// collects a `Vec` of every match and takes its minimum the way
+    /// `matches().into_iter().min()` would.
+    pub fn matches_priority<P: AsRef<Path>>(&self, path: P) -> Option<usize> {
+        self.first_match(path)
+    }
+
     /// Returns the sequence number of every glob pattern that matches the
     /// given path.
     ///
@@ -271,82 +1043,618 @@ impl GlobSet {
         into
     }
 
-    /// Adds the sequence number of every glob pattern that matches the given
-    /// path to the vec given.
+    /// Returns a boolean mask of length `len()`, where index `i` is `true`
+    /// if and only if `matches` would include `i`.
     ///
-    /// `into` is is cleared before matching begins, and contains the set of
-    /// sequence numbers (in ascending order) after matching ends. If no globs
-    /// were matched, then `into` will be empty.
-    pub fn matches_into<P: AsRef<Path>>(
+    /// This suits columnar processing that visits every glob regardless,
+    /// e.g. zipping the mask against a parallel per-glob `Vec` of actions,
+    /// where looking up membership in a sparse `Vec<usize>` for every glob
+    /// would mean an allocation-free scan turning into repeated linear
+    /// searches.
+    pub fn match_mask<P: AsRef<Path>>(&self, path: P) -> Vec<bool> {
+        self.match_mask_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Returns a boolean mask of length `len()`, where index `i` is `true`
+    /// if and only if `matches_candidate` would include `i`.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn match_mask_candidate(&self, path: &Candidate) -> Vec<bool> {
+        let mut mask = vec![false; self.len()];
+        for i in self.matches_candidate(path) {
+            mask[i] = true;
+        }
+        mask
+    }
+
+    /// Returns the sequence number of every glob pattern that matches the
+    /// given path.
+    ///
+    /// Like `matches`, but pre-allocates the returned `Vec` with room for
+    /// `cap` matches, which avoids reallocation as strategies push results
+    /// when the caller already knows a path is likely to match many globs
+    /// in this set.
+    pub fn matches_capacity<P: AsRef<Path>>(
         &self,
         path: P,
-        into: &mut Vec<usize>,
-    ) {
-        self.matches_candidate_into(&Candidate::new(path.as_ref()), into);
+        cap: usize,
+    ) -> Vec<usize> {
+        self.matches_candidate_capacity(&Candidate::new(path.as_ref()), cap)
     }
 
-    /// Adds the sequence number of every glob pattern that matches the given
-    /// path to the vec given.
+    /// Returns the sequence number of every glob pattern that matches the
+    /// given candidate.
     ///
-    /// `into` is is cleared before matching begins, and contains the set of
-    /// sequence numbers (in ascending order) after matching ends. If no globs
-    /// were matched, then `into` will be empty.
+    /// Like `matches_candidate`, but pre-allocates the returned `Vec` with
+    /// room for `cap` matches, which avoids reallocation as strategies push
+    /// results when the caller already knows a path is likely to match many
+    /// globs in this set.
+    pub fn matches_candidate_capacity(
+        &self,
+        path: &Candidate,
+        cap: usize,
+    ) -> Vec<usize> {
+        let mut into = Vec::with_capacity(cap);
+        if self.is_empty() {
+            return into;
+        }
+        self.matches_candidate_into(path, &mut into);
+        into
+    }
+
+    /// Returns the sequence number of every glob pattern that matches the
+    /// given path and for which `include` returns `true`.
+    ///
+    /// Every strategy still runs over the full set; `include` only filters
+    /// which of their hits are kept. This is meant for toggling rules on
+    /// and off at runtime (e.g. `include(i) = enabled[i]`) without paying
+    /// to rebuild a smaller `GlobSet` from scratch.
+    pub fn matches_subset<P, F>(&self, path: P, include: F) -> Vec<usize>
+            where P: AsRef<Path>, F: Fn(usize) -> bool {
+        self.matches_subset_candidate(&Candidate::new(path.as_ref()), include)
+    }
+
+    /// Returns the sequence number of every glob pattern that matches the
+    /// given candidate and for which `include` returns `true`.
     ///
     /// This takes a Candidate as input, which can be used to amortize the
     /// cost of preparing a path for matching.
-    pub fn matches_candidate_into(
+    pub fn matches_subset_candidate<F>(
         &self,
         path: &Candidate,
-        into: &mut Vec<usize>,
-    ) {
-        into.clear();
+        include: F,
+    ) -> Vec<usize>
+            where F: Fn(usize) -> bool {
+        self.matches_candidate(path).into_iter().filter(|&i| include(i)).collect()
+    }
+
+    /// Returns the number of glob patterns that match the given path.
+    pub fn count_matches<P: AsRef<Path>>(&self, path: P) -> usize {
+        self.count_matches_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Returns the number of glob patterns that match the given candidate.
+    ///
+    /// Unlike `matches_candidate`, this never materializes the sequence
+    /// numbers that matched; it only counts them. A scratch bitset (sized
+    /// to this set's length) is used to dedup across strategies, since a
+    /// single pattern can be reachable from more than one strategy and
+    /// naively summing each strategy's hit count would double-count it.
+    pub fn count_matches_candidate(&self, path: &Candidate) -> usize {
         if self.is_empty() {
-            return;
+            return 0;
         }
+        let mut buf = vec![];
+        let mut seen = vec![false; self.len];
+        let mut count = 0;
         for strat in &self.strats {
-            strat.matches_into(path, into);
+            buf.clear();
+            strat.matches_into(path, &mut buf);
+            for &i in &buf {
+                if self.enabled[i] && !seen[i] {
+                    seen[i] = true;
+                    count += 1;
+                }
+            }
         }
-        into.sort();
-        into.dedup();
+        count
     }
 
-    fn new(pats: &[Glob]) -> Result<GlobSet, Error> {
-        if pats.is_empty() {
-            return Ok(GlobSet { len: 0, strats: vec![] });
-        }
-        let mut lits = LiteralStrategy::new();
-        let mut base_lits = BasenameLiteralStrategy::new();
-        let mut exts = ExtensionStrategy::new();
-        let mut prefixes = MultiStrategyBuilder::new();
-        let mut suffixes = MultiStrategyBuilder::new();
-        let mut required_exts = RequiredExtensionStrategyBuilder::new();
+    /// Returns, for every glob pattern that matches `path`, which internal
+    /// strategy reported the match.
+    ///
+    /// This is meant for diagnosing why a `GlobSet` is slow, by turning the
+    /// internal `debug!` logging done at build time into something
+    /// programmatically inspectable at match time.
+    pub fn explain<P: AsRef<Path>>(&self, path: P) -> Vec<(usize, MatchSource)> {
+        self.explain_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Returns, for every glob pattern that matches `path`, which internal
+    /// strategy reported the match.
+    ///
+    /// A pattern can appear more than once if more than one strategy
+    /// confirms a match for it, which happens for suffix patterns anchored
+    /// to a full path component (e.g. `*/foo`); these are also registered
+    /// as literals. Unlike `matches_candidate`, results here are neither
+    /// deduplicated nor sorted by sequence number.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn explain_candidate(
+        &self,
+        path: &Candidate,
+    ) -> Vec<(usize, MatchSource)> {
+        let mut out = vec![];
+        if self.is_empty() {
+            return out;
+        }
+        let mut buf = vec![];
+        for strat in &self.strats {
+            let source = MatchSource::of(strat);
+            buf.clear();
+            strat.matches_into(path, &mut buf);
+            for &i in &buf {
+                if self.enabled[i] {
+                    out.push((i, source));
+                }
+            }
+        }
+        out
+    }
+
+    /// Returns, for every pattern in this set in sequence order, which
+    /// internal strategy it was compiled into.
+    ///
+    /// Unlike `explain`, this needs no candidate path: the classification
+    /// happened once at `build` time and never changes afterward. This is
+    /// meant for tuning a set of patterns, e.g. to see which ones fell back
+    /// to the comparatively slow regex strategy.
+    pub fn strategies(&self) -> Strategies {
+        Strategies { set: self, idx: 0 }
+    }
+
+    /// Returns size and complexity metrics for this set, for capacity
+    /// planning.
+    ///
+    /// The per-strategy counts come from the same classification
+    /// `strategies` reports. `approx_bytes` is a rough estimate of the
+    /// space taken up by the compiled regex programs and literal automata
+    /// backing this set's strategies, derived from the length of each
+    /// pattern's source text rather than by inspecting the automata
+    /// themselves (neither `aho_corasick` nor `regex` exposes that in this
+    /// crate's version); treat it as useful for comparing sets against each
+    /// other, not as an exact byte count.
+    pub fn stats(&self) -> GlobSetStats {
+        let mut stats = GlobSetStats::default();
+        for source in self.strategies() {
+            match source {
+                MatchSource::Literal => stats.literals += 1,
+                MatchSource::BasenameLiteral => stats.basename_literals += 1,
+                MatchSource::Extension => stats.extensions += 1,
+                MatchSource::Prefix => stats.prefixes += 1,
+                MatchSource::Suffix => stats.suffixes += 1,
+                MatchSource::RequiredExtension => stats.required_extensions += 1,
+                MatchSource::Regex => stats.regexes += 1,
+            }
+        }
+        for p in &self.pats {
+            stats.approx_bytes += match *p {
+                Pattern::Glob(ref g) => g.regex().len(),
+                Pattern::Regex(ref re) => re.len(),
+            };
+        }
+        stats
+    }
+
+    /// Returns a human-readable listing of every pattern in this set and
+    /// the strategy it was classified into at build time, one per line,
+    /// e.g. `[0] *.rs (Extension)`.
+    ///
+    /// The derived `Debug` for `GlobSet` dumps the opaque internals of
+    /// each strategy's lookup table or automaton, which is useless for
+    /// spot-checking that a config file's patterns compiled the way you
+    /// expected. This is meant for logging and troubleshooting instead.
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        for (i, p) in self.pats.iter().enumerate() {
+            let text = match *p {
+                Pattern::Glob(ref g) => g.glob(),
+                Pattern::Regex(ref re) => re,
+            };
+            out.push_str(&format!(
+                "[{}] {} ({:?})\n", i, text, self.strategies[i]));
+        }
+        out
+    }
+
+    /// Returns the union of file extensions this set's patterns could
+    /// possibly match, or `None` if any pattern isn't scoped to a specific
+    /// extension and so could match a path with any extension (or none).
+    ///
+    /// This only looks at patterns classified into the `Extension` or
+    /// `RequiredExtension` strategies; the presence of even one pattern in
+    /// any other strategy (a literal path, a bare prefix/suffix, or a raw
+    /// regex) makes the set's extension coverage impossible to bound, so
+    /// this bails out to `None` rather than reporting a partial, misleading
+    /// set. This is meant for a file watcher that wants to narrow its
+    /// subscription to only the extensions a set actually cares about.
+    pub fn interesting_extensions(&self) -> Option<HashSet<OsString>> {
+        for source in self.strategies() {
+            match source {
+                MatchSource::Extension | MatchSource::RequiredExtension => {}
+                _ => return None,
+            }
+        }
+        let mut exts = HashSet::new();
+        for strat in &self.strats {
+            match *strat {
+                GlobSetMatchStrategy::Extension(ref s) => {
+                    exts.extend(s.0.keys().cloned());
+                }
+                GlobSetMatchStrategy::RequiredExtension(ref s) => {
+                    exts.extend(s.0.keys().cloned());
+                }
+                _ => {}
+            }
+        }
+        Some(exts)
+    }
+
+    /// Returns a `MatchTracker` for recording which globs in this set have
+    /// matched at least one path across a batch of matching calls.
+    ///
+    /// This is meant for reporting "pattern X matched no files" once a
+    /// batch is done, without callers having to maintain their own `Vec<bool>`
+    /// and wire it through every matching call themselves.
+    pub fn matched_tracker(&self) -> MatchTracker {
+        MatchTracker { set: self, seen: vec![false; self.len] }
+    }
+
+    /// Returns the sequence number of the most specific glob pattern that
+    /// matches `path`, or `None` if nothing matches.
+    ///
+    /// "Most specific" is scored per pattern as `(literal prefix length,
+    /// wildcard count)`, comparing the longer literal prefix first and
+    /// breaking ties by whichever pattern has fewer of `*`, `?`, `[`, or
+    /// `{`. So `src/lib.rs` (an 11-byte prefix, no wildcards) beats `*.rs`
+    /// (an empty prefix) for the path `src/lib.rs`, which makes this handy
+    /// as a routing table for config systems that want the single
+    /// most-specific rule rather than every rule that happens to match. A
+    /// raw regex added via `GlobSetBuilder::add_regex` always scores as the
+    /// least specific pattern, having no literal prefix to speak of. Ties
+    /// that remain after both criteria go to whichever pattern was added
+    /// first.
+    pub fn most_specific_match<P: AsRef<Path>>(&self, path: P) -> Option<usize> {
+        self.most_specific_match_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Returns the sequence number of the most specific glob pattern that
+    /// matches `path`, or `None` if nothing matches. See `most_specific_match`
+    /// for how specificity is scored.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn most_specific_match_candidate(
+        &self,
+        path: &Candidate,
+    ) -> Option<usize> {
+        let mut best: Option<(usize, (usize, usize))> = None;
+        for i in self.matches_candidate(path) {
+            let score = self.specificity(i);
+            let is_better = match best {
+                None => true,
+                Some((_, best_score)) => {
+                    score.0 > best_score.0
+                        || (score.0 == best_score.0 && score.1 < best_score.1)
+                }
+            };
+            if is_better {
+                best = Some((i, score));
+            }
+        }
+        best.map(|(i, _)| i)
+    }
+
+    /// Returns `(literal prefix length, wildcard count)` for the pattern at
+    /// sequence number `i`, used by `most_specific_match_candidate` to rank
+    /// matches. See `most_specific_match` for what these mean.
+    fn specificity(&self, i: usize) -> (usize, usize) {
+        match self.pats[i] {
+            Pattern::Glob(ref g) => {
+                let prefix_len = g.literal_prefix().len();
+                let wildcards = g.glob()
+                    .chars()
+                    .filter(|&c| c == '*' || c == '?' || c == '[' || c == '{')
+                    .count();
+                (prefix_len, wildcards)
+            }
+            Pattern::Regex(_) => (0, usize::max_value()),
+        }
+    }
+
+    /// Matches every path in `paths` against this set in parallel, using
+    /// as many threads as rayon's global pool has available.
+    ///
+    /// Returns the sequence numbers that matched each path, in the same
+    /// order as `paths`, exactly as `matches` would report them one at a
+    /// time. `GlobSet`'s strategies are immutable and `Sync` once built, so
+    /// matching many paths concurrently needs no additional
+    /// synchronization. Requires the `rayon` feature.
+    #[cfg(feature = "rayon")]
+    pub fn par_matches<P>(&self, paths: &[P]) -> Vec<Vec<usize>>
+            where P: AsRef<Path> + Sync {
+        paths.par_iter()
+            .map(|p| self.matches_candidate(&Candidate::new(p.as_ref())))
+            .collect()
+    }
+
+    /// Returns the sequence number of every glob pattern that matches the
+    /// given path, using inline storage instead of a heap-allocated `Vec`
+    /// for the (common) case of `MATCHES_SMALLVEC_INLINE_SIZE` or fewer
+    /// matches. Requires the `smallvec` feature.
+    #[cfg(feature = "smallvec")]
+    pub fn matches_smallvec<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> SmallVec<[usize; MATCHES_SMALLVEC_INLINE_SIZE]> {
+        self.matches_smallvec_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Returns the sequence number of every glob pattern that matches the
+    /// given path, using inline storage instead of a heap-allocated `Vec`
+    /// for the (common) case of `MATCHES_SMALLVEC_INLINE_SIZE` or fewer
+    /// matches.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching. Requires the `smallvec`
+    /// feature.
+    #[cfg(feature = "smallvec")]
+    pub fn matches_smallvec_candidate(
+        &self,
+        path: &Candidate,
+    ) -> SmallVec<[usize; MATCHES_SMALLVEC_INLINE_SIZE]> {
+        let mut into = SmallVec::new();
+        if self.is_empty() {
+            return into;
+        }
+        self.for_each_match_candidate(path, &mut |i| into.push(i));
+        into.sort();
+        into.dedup();
+        if !self.all_enabled() {
+            into.retain(|&i| self.enabled[i]);
+        }
+        into
+    }
+
+    /// Adds the sequence number of every glob pattern that matches the given
+    /// path to the vec given.
+    ///
+    /// `into` is is cleared before matching begins, and contains the set of
+    /// sequence numbers (in ascending order) after matching ends. If no globs
+    /// were matched, then `into` will be empty.
+    pub fn matches_into<P: AsRef<Path>>(
+        &self,
+        path: P,
+        into: &mut Vec<usize>,
+    ) {
+        self.matches_candidate_into(&Candidate::new(path.as_ref()), into);
+    }
+
+    /// Adds the sequence number of every glob pattern that matches the given
+    /// path to the vec given.
+    ///
+    /// `into` is is cleared before matching begins, and contains the set of
+    /// sequence numbers (in ascending order) after matching ends. If no globs
+    /// were matched, then `into` will be empty.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn matches_candidate_into(
+        &self,
+        path: &Candidate,
+        into: &mut Vec<usize>,
+    ) {
+        into.clear();
+        if self.is_empty() {
+            return;
+        }
+        // Track how many strategies actually contributed matches. When it's
+        // at most one, `into` is already in the order that single strategy
+        // produced it in, which (for every strategy in this crate) is
+        // ascending by construction; skip the sort/dedup pass in that case,
+        // since it's pure overhead for the common single-strategy hit.
+        let mut strategies_hit = 0;
+        for strat in &self.strats {
+            let before = into.len();
+            strat.matches_into(path, into);
+            if into.len() > before {
+                strategies_hit += 1;
+            }
+        }
+        if strategies_hit > 1 || !is_sorted_ascending(into) {
+            into.sort();
+            into.dedup();
+        }
+        if !self.all_enabled() {
+            into.retain(|&i| self.enabled[i]);
+        }
+    }
+
+    /// Adds the sequence number of every glob pattern that matches the
+    /// given path to `into`, skipping the sort/dedup pass `matches_into`
+    /// does.
+    ///
+    /// `into` is cleared before matching begins. Unlike `matches_into`, the
+    /// result is in whatever order the internal strategies happen to
+    /// produce it, and a pattern may appear more than once if more than one
+    /// strategy matches it; callers who don't care about order and would
+    /// rather do their own deduplication (e.g. into a `HashSet` they
+    /// already need downstream) can skip paying for the sort/dedup pass
+    /// here. Use `for_each_match`/`for_each_match_candidate` instead if you
+    /// don't need a `Vec` at all.
+    pub fn matches_unsorted<P: AsRef<Path>>(
+        &self,
+        path: P,
+        into: &mut Vec<usize>,
+    ) {
+        self.matches_unsorted_candidate(&Candidate::new(path.as_ref()), into);
+    }
+
+    /// Adds the sequence number of every glob pattern that matches the
+    /// given path to `into`, skipping the sort/dedup pass
+    /// `matches_candidate_into` does. See `matches_unsorted` for details.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn matches_unsorted_candidate(
+        &self,
+        path: &Candidate,
+        into: &mut Vec<usize>,
+    ) {
+        into.clear();
+        if self.is_empty() {
+            return;
+        }
+        for strat in &self.strats {
+            strat.matches_into(path, into);
+        }
+    }
+
+    /// Calls the given closure with the sequence number of every glob
+    /// pattern that matches the given path, without allocating.
+    ///
+    /// Unlike `matches_into`, this does not sort or dedup the reported
+    /// sequence numbers, and a pattern may be reported more than once if
+    /// more than one internal strategy matches it.
+    pub fn for_each_match<P: AsRef<Path>>(
+        &self,
+        path: P,
+        f: &mut FnMut(usize),
+    ) {
+        self.for_each_match_candidate(&Candidate::new(path.as_ref()), f);
+    }
+
+    /// Calls the given closure with the sequence number of every glob
+    /// pattern that matches the given path, without allocating.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    ///
+    /// Unlike `matches_candidate_into`, this does not sort or dedup the
+    /// reported sequence numbers, and a pattern may be reported more than
+    /// once if more than one internal strategy matches it.
+    pub fn for_each_match_candidate(
+        &self,
+        path: &Candidate,
+        f: &mut FnMut(usize),
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        for strat in &self.strats {
+            strat.for_each_match(path, f);
+        }
+    }
+
+    /// Returns a lazy iterator over the sequence number of every glob
+    /// pattern that matches the given candidate.
+    ///
+    /// This takes a `Candidate` as input (rather than a plain path), since
+    /// the iterator borrows it for as long as it's alive, which lets the
+    /// caller amortize the cost of preparing a path for matching across
+    /// several calls.
+    ///
+    /// Like `for_each_match_candidate`, this does not sort or dedup the
+    /// reported sequence numbers, and a pattern may be yielded more than
+    /// once if more than one internal strategy matches it. Unlike
+    /// `matches_candidate`, this only ever holds a single small buffer for
+    /// whichever strategy is currently being drained, instead of collecting
+    /// every match up front.
+    pub fn matches_candidate_iter<'a>(
+        &'a self,
+        path: &'a Candidate<'a>,
+    ) -> GlobSetMatches<'a> {
+        GlobSetMatches {
+            set: self,
+            candidate: path,
+            strat_idx: 0,
+            buf: vec![],
+            buf_idx: 0,
+        }
+    }
+
+    fn new(
+        pats: &[Pattern],
+        negated: &[bool],
+        tags: &[Option<String>],
+        size_limit: usize,
+        dfa_size_limit: usize,
+    ) -> Result<GlobSet, Error> {
+        if pats.is_empty() {
+            return Ok(GlobSet::empty());
+        }
+        let mut lits = LiteralStrategy::new();
+        let mut base_lits = BasenameLiteralStrategy::new();
+        let mut exts = ExtensionStrategy::new();
+        let mut prefixes = MultiStrategyBuilder::new();
+        let mut suffixes = MultiStrategyBuilder::new();
+        let mut required_exts = RequiredExtensionStrategyBuilder::new();
         let mut regexes = MultiStrategyBuilder::new();
+        let mut strategies = Vec::with_capacity(pats.len());
         for (i, p) in pats.iter().enumerate() {
+            let p = match *p {
+                Pattern::Glob(ref p) => p,
+                Pattern::Regex(ref re) => {
+                    debug!("raw regex added to set: {:?}", re);
+                    regexes.add(i, re.to_owned());
+                    strategies.push(MatchSource::Regex);
+                    continue;
+                }
+            };
             match MatchStrategy::new(p) {
                 MatchStrategy::Literal(lit) => {
                     lits.add(i, lit);
+                    strategies.push(MatchSource::Literal);
+                }
+                MatchStrategy::Literals(branches) => {
+                    for lit in branches {
+                        lits.add(i, lit);
+                    }
+                    strategies.push(MatchSource::Literal);
                 }
                 MatchStrategy::BasenameLiteral(lit) => {
                     base_lits.add(i, lit);
+                    strategies.push(MatchSource::BasenameLiteral);
                 }
                 MatchStrategy::Extension(ext) => {
                     exts.add(i, ext);
+                    strategies.push(MatchSource::Extension);
+                }
+                MatchStrategy::CompoundExtension(suffix) => {
+                    suffixes.add(i, suffix);
+                    strategies.push(MatchSource::Suffix);
                 }
                 MatchStrategy::Prefix(prefix) => {
                     prefixes.add(i, prefix);
+                    strategies.push(MatchSource::Prefix);
                 }
                 MatchStrategy::Suffix { suffix, component } => {
                     if component {
                         lits.add(i, suffix[1..].to_string());
                     }
                     suffixes.add(i, suffix);
+                    strategies.push(MatchSource::Suffix);
                 }
                 MatchStrategy::RequiredExtension(ext) => {
                     required_exts.add(i, ext, p.regex().to_owned());
+                    strategies.push(MatchSource::RequiredExtension);
                 }
                 MatchStrategy::Regex => {
                     debug!("glob converted to regex: {:?}", p);
                     regexes.add(i, p.regex().to_owned());
+                    strategies.push(MatchSource::Regex);
                 }
             }
         }
@@ -363,425 +1671,2691 @@ impl GlobSet {
                 GlobSetMatchStrategy::Literal(lits),
                 GlobSetMatchStrategy::Suffix(suffixes.suffix()),
                 GlobSetMatchStrategy::Prefix(prefixes.prefix()),
-                GlobSetMatchStrategy::RequiredExtension(
-                    try!(required_exts.build())),
-                GlobSetMatchStrategy::Regex(try!(regexes.regex_set())),
+                GlobSetMatchStrategy::RequiredExtension(try!(
+                    required_exts.build(size_limit, dfa_size_limit))),
+                GlobSetMatchStrategy::Regex(try!(
+                    regexes.regex_set(size_limit, dfa_size_limit))),
             ],
+            negated: negated.to_vec(),
+            enabled: vec![true; pats.len()],
+            globs: pats.iter()
+                .filter_map(|p| match *p {
+                    Pattern::Glob(ref g) => Some(g.clone()),
+                    Pattern::Regex(_) => None,
+                })
+                .collect(),
+            pats: pats.to_vec(),
+            strategies: strategies,
+            tags: tags.to_vec(),
         })
     }
+
+    /// Returns the tag given to the pattern at `index` via
+    /// `GlobSetBuilder::add_tagged`, or `None` if it wasn't tagged.
+    ///
+    /// This is meant for the common case of just wanting to know which
+    /// caller owns a matched pattern, e.g. reporting `("lint", 3)` when
+    /// several tools share one `GlobSet`; a caller needing a full value per
+    /// pattern instead of a single label string should attach its own
+    /// side table keyed by sequence number.
+    pub fn tag(&self, index: usize) -> Option<&str> {
+        self.tags.get(index).and_then(|t| t.as_ref()).map(|s| s.as_str())
+    }
 }
 
-/// GlobSetBuilder builds a group of patterns that can be used to
-/// simultaneously match a file path.
-pub struct GlobSetBuilder {
-    pats: Vec<Glob>,
+impl Default for GlobSet {
+    /// Returns an empty `GlobSet`, matching nothing. Equivalent to
+    /// `GlobSet::empty()`.
+    fn default() -> GlobSet {
+        GlobSet::empty()
+    }
 }
 
-impl GlobSetBuilder {
-    /// Create a new GlobSetBuilder. A GlobSetBuilder can be used to add new
-    /// patterns. Once all patterns have been added, `build` should be called
-    /// to produce a `GlobSet`, which can then be used for matching.
-    pub fn new() -> GlobSetBuilder {
-        GlobSetBuilder { pats: vec![] }
+#[cfg(feature = "serde1")]
+impl<'de> ::serde::Deserialize<'de> for GlobSet {
+    fn deserialize<D>(deserializer: D) -> Result<GlobSet, D::Error>
+            where D: ::serde::Deserializer<'de> {
+        let builder = try!(GlobSetBuilder::deserialize(deserializer));
+        builder.build().map_err(::serde::de::Error::custom)
     }
+}
 
-    /// Builds a new matcher from all of the glob patterns added so far.
-    ///
-    /// Once a matcher is built, no new patterns can be added to it.
-    pub fn build(&self) -> Result<GlobSet, Error> {
-        GlobSet::new(&self.pats)
+/// A lazy iterator over the sequence numbers of the globs in a `GlobSet`
+/// that match a candidate, created by `GlobSet::matches_candidate_iter`.
+///
+/// Internally, this drains a single small buffer of matches from whichever
+/// strategy is currently active, refilling it from the next strategy once
+/// it runs dry, rather than collecting every strategy's matches into one
+/// `Vec` up front.
+pub struct GlobSetMatches<'a> {
+    set: &'a GlobSet,
+    candidate: &'a Candidate<'a>,
+    strat_idx: usize,
+    buf: Vec<usize>,
+    buf_idx: usize,
+}
+
+impl<'a> Iterator for GlobSetMatches<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            if self.buf_idx < self.buf.len() {
+                let i = self.buf[self.buf_idx];
+                self.buf_idx += 1;
+                if self.set.enabled[i] {
+                    return Some(i);
+                }
+                continue;
+            }
+            if self.strat_idx >= self.set.strats.len() {
+                return None;
+            }
+            self.buf.clear();
+            self.buf_idx = 0;
+            self.set.strats[self.strat_idx]
+                .matches_into(self.candidate, &mut self.buf);
+            self.strat_idx += 1;
+        }
     }
+}
 
-    /// Add a new pattern to this set.
-    #[allow(dead_code)]
-    pub fn add(&mut self, pat: Glob) -> &mut GlobSetBuilder {
-        self.pats.push(pat);
-        self
+/// A lazy iterator over `(sequence number, MatchSource)` pairs describing
+/// which strategy each pattern in a `GlobSet` was compiled into, created by
+/// `GlobSet::strategies`.
+pub struct Strategies<'a> {
+    set: &'a GlobSet,
+    idx: usize,
+}
+
+impl<'a> Iterator for Strategies<'a> {
+    type Item = (usize, MatchSource);
+
+    fn next(&mut self) -> Option<(usize, MatchSource)> {
+        match self.set.strategies.get(self.idx) {
+            None => None,
+            Some(&source) => {
+                let i = self.idx;
+                self.idx += 1;
+                Some((i, source))
+            }
+        }
     }
 }
 
-/// A candidate path for matching.
+/// Records which globs in a `GlobSet` have matched at least one path across
+/// a batch of matching calls, created by `GlobSet::matched_tracker`.
 ///
-/// All glob matching in this crate operates on `Candidate` values.
-/// Constructing candidates has a very small cost associated with it, so
-/// callers may find it beneficial to amortize that cost when matching a single
-/// path against multiple globs or sets of globs.
-#[derive(Clone, Debug)]
-pub struct Candidate<'a> {
-    path: Cow<'a, [u8]>,
-    basename: Cow<'a, [u8]>,
-    ext: &'a OsStr,
+/// This exists so a caller processing many paths against the same set, e.g.
+/// walking a directory tree, doesn't have to maintain its own `Vec<bool>`
+/// (one entry per glob) and thread it through every matching call just to
+/// report "pattern X never matched anything" once the batch is done.
+pub struct MatchTracker<'a> {
+    set: &'a GlobSet,
+    seen: Vec<bool>,
 }
 
-impl<'a> Candidate<'a> {
-    /// Create a new candidate for matching from the given path.
-    pub fn new<P: AsRef<Path> + ?Sized>(path: &'a P) -> Candidate<'a> {
-        let path = path.as_ref();
-        let basename = file_name(path).unwrap_or(OsStr::new(""));
-        Candidate {
-            path: normalize_path(path_bytes(path)),
-            basename: os_str_bytes(basename),
-            ext: file_name_ext(basename).unwrap_or(OsStr::new("")),
-        }
+impl<'a> MatchTracker<'a> {
+    /// Returns true if any glob in the underlying set matches `path`,
+    /// recording every glob that did so as matched.
+    pub fn is_match<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        self.is_match_candidate(&Candidate::new(path.as_ref()))
     }
 
-    fn path_prefix(&self, max: usize) -> &[u8] {
-        if self.path.len() <= max {
-            &*self.path
-        } else {
-            &self.path[..max]
+    /// Returns true if any glob in the underlying set matches `path`,
+    /// recording every glob that did so as matched.
+    ///
+    /// This takes a Candidate as input, which can be used to amortize the
+    /// cost of preparing a path for matching.
+    pub fn is_match_candidate(&mut self, path: &Candidate) -> bool {
+        let mut matches = vec![];
+        self.set.matches_candidate_into(path, &mut matches);
+        for &i in &matches {
+            self.seen[i] = true;
         }
+        !matches.is_empty()
     }
 
-    fn path_suffix(&self, max: usize) -> &[u8] {
-        if self.path.len() <= max {
-            &*self.path
-        } else {
-            &self.path[self.path.len() - max..]
-        }
+    /// Returns the sequence numbers of every glob that hasn't matched any
+    /// path passed to `is_match`/`is_match_candidate` so far, in ascending
+    /// order.
+    pub fn unmatched(&self) -> Vec<usize> {
+        self.seen
+            .iter()
+            .enumerate()
+            .filter(|&(_, &matched)| !matched)
+            .map(|(i, _)| i)
+            .collect()
     }
 }
 
+/// A single pattern added to a `GlobSetBuilder`.
+///
+/// Most patterns are ordinary globs, but a `GlobSetBuilder` can also mix in
+/// already-anchored raw regexes so that both kinds of patterns are matched
+/// in the same single pass and share the same sequence number space.
 #[derive(Clone, Debug)]
-enum GlobSetMatchStrategy {
-    Literal(LiteralStrategy),
-    BasenameLiteral(BasenameLiteralStrategy),
-    Extension(ExtensionStrategy),
-    Prefix(PrefixStrategy),
-    Suffix(SuffixStrategy),
-    RequiredExtension(RequiredExtensionStrategy),
-    Regex(RegexSetStrategy),
+enum Pattern {
+    Glob(Glob),
+    Regex(String),
 }
 
-impl GlobSetMatchStrategy {
-    fn is_match(&self, candidate: &Candidate) -> bool {
-        use self::GlobSetMatchStrategy::*;
-        match *self {
-            Literal(ref s) => s.is_match(candidate),
-            BasenameLiteral(ref s) => s.is_match(candidate),
-            Extension(ref s) => s.is_match(candidate),
-            Prefix(ref s) => s.is_match(candidate),
-            Suffix(ref s) => s.is_match(candidate),
-            RequiredExtension(ref s) => s.is_match(candidate),
-            Regex(ref s) => s.is_match(candidate),
-        }
-    }
+/// GlobSetBuilder builds a group of patterns that can be used to
+/// simultaneously match a file path.
+pub struct GlobSetBuilder {
+    pats: Vec<Pattern>,
+    negated: Vec<bool>,
+    tags: Vec<Option<String>>,
+    case_insensitive: bool,
+    regex_size_limit: usize,
+    dfa_size_limit: usize,
+}
 
-    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
-        use self::GlobSetMatchStrategy::*;
-        match *self {
-            Literal(ref s) => s.matches_into(candidate, matches),
-            BasenameLiteral(ref s) => s.matches_into(candidate, matches),
-            Extension(ref s) => s.matches_into(candidate, matches),
-            Prefix(ref s) => s.matches_into(candidate, matches),
-            Suffix(ref s) => s.matches_into(candidate, matches),
-            RequiredExtension(ref s) => s.matches_into(candidate, matches),
-            Regex(ref s) => s.matches_into(candidate, matches),
+impl GlobSetBuilder {
+    /// Create a new GlobSetBuilder. A GlobSetBuilder can be used to add new
+    /// patterns. Once all patterns have been added, `build` should be called
+    /// to produce a `GlobSet`, which can then be used for matching.
+    pub fn new() -> GlobSetBuilder {
+        GlobSetBuilder {
+            pats: vec![],
+            negated: vec![],
+            tags: vec![],
+            case_insensitive: false,
+            regex_size_limit: DEFAULT_SIZE_LIMIT,
+            dfa_size_limit: DEFAULT_SIZE_LIMIT,
         }
     }
-}
-
-#[derive(Clone, Debug)]
-struct LiteralStrategy(BTreeMap<Vec<u8>, Vec<usize>>);
 
-impl LiteralStrategy {
-    fn new() -> LiteralStrategy {
-        LiteralStrategy(BTreeMap::new())
+    /// Sets whether every glob in this set matches case insensitively.
+    ///
+    /// Unlike `GlobBuilder::case_insensitive`, this is a single flag applied
+    /// to the whole set at `build` time rather than one that has to be set
+    /// on each `Glob` individually, so it's convenient when loading a large
+    /// number of patterns from a config file. Turning this on causes `build`
+    /// to recompile every glob pattern added so far (and any added later)
+    /// with case insensitivity forced on, which discards any other option
+    /// (e.g. `literal_separator`) set directly on those globs; patterns
+    /// added via `add_regex`/`add_negated_regex` are unaffected, since a raw
+    /// regex is compiled as-is. Default is off.
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut GlobSetBuilder {
+        self.case_insensitive = yes;
+        self
     }
 
-    fn add(&mut self, global_index: usize, lit: String) {
-        self.0.entry(lit.into_bytes()).or_insert(vec![]).push(global_index);
+    /// Sets the size limit, in bytes, placed on a single compiled regex
+    /// program built while assembling this set (whether from a glob that
+    /// fell back to the regex strategy, or one added via `add_regex`).
+    ///
+    /// Raise this if `build` fails with `ErrorKind::Regex` on a pattern
+    /// that's merely large (e.g. a brace expansion with hundreds of
+    /// branches) rather than actually malformed. Default is 10 MiB.
+    pub fn regex_size_limit(&mut self, limit: usize) -> &mut GlobSetBuilder {
+        self.regex_size_limit = limit;
+        self
     }
 
-    fn is_match(&self, candidate: &Candidate) -> bool {
-        self.0.contains_key(&*candidate.path)
+    /// Sets the size limit, in bytes, placed on the lazy DFA cache used by
+    /// a single compiled regex built while assembling this set. Default is
+    /// 10 MiB.
+    pub fn dfa_size_limit(&mut self, limit: usize) -> &mut GlobSetBuilder {
+        self.dfa_size_limit = limit;
+        self
     }
 
-    #[inline(never)]
-    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
-        if let Some(hits) = self.0.get(&*candidate.path) {
-            matches.extend(hits);
+    /// Builds a new matcher from all of the glob patterns added so far.
+    ///
+    /// Once a matcher is built, no new patterns can be added to it.
+    pub fn build(&self) -> Result<GlobSet, Error> {
+        if !self.case_insensitive {
+            return GlobSet::new(
+                &self.pats, &self.negated, &self.tags,
+                self.regex_size_limit, self.dfa_size_limit);
         }
+        let pats: Vec<Pattern> = try!(self.pats
+            .iter()
+            .map(|p| match *p {
+                Pattern::Glob(ref g) => {
+                    GlobBuilder::new(g.glob())
+                        .case_insensitive(true)
+                        .build()
+                        .map(Pattern::Glob)
+                }
+                Pattern::Regex(ref r) => Ok(Pattern::Regex(r.clone())),
+            })
+            .collect());
+        GlobSet::new(
+            &pats, &self.negated, &self.tags,
+            self.regex_size_limit, self.dfa_size_limit)
     }
-}
 
-#[derive(Clone, Debug)]
-struct BasenameLiteralStrategy(BTreeMap<Vec<u8>, Vec<usize>>);
+    /// Builds a new matcher from all of the glob patterns added so far, the
+    /// same way `build` does, but also reports how each pattern's sequence
+    /// number changed relative to `previous`.
+    ///
+    /// Patterns are matched up across the two sets by comparing original
+    /// pattern text (and, for a glob, its negated flag), not by index, so
+    /// callers doing incremental recompilation can use the returned
+    /// `IndexChange`s to migrate any downstream state they keep keyed by
+    /// sequence number (e.g. per-pattern statistics or `set_enabled` state)
+    /// onto the new set, rather than rebuilding it from scratch.
+    ///
+    /// If the same pattern text (and negated flag) appears more than once
+    /// in either set, duplicates are paired off in the order they appear,
+    /// same as a stable multiset union.
+    pub fn build_diff(
+        &self,
+        previous: &GlobSet,
+    ) -> Result<(GlobSet, Vec<IndexChange>), Error> {
+        let new_set = try!(self.build());
 
-impl BasenameLiteralStrategy {
-    fn new() -> BasenameLiteralStrategy {
-        BasenameLiteralStrategy(BTreeMap::new())
-    }
+        let mut old_by_key: HashMap<(bool, &str, bool), Vec<usize>> =
+            HashMap::new();
+        for (i, p) in previous.pats.iter().enumerate() {
+            old_by_key
+                .entry(pattern_key(p, previous.negated[i]))
+                .or_insert_with(Vec::new)
+                .push(i);
+        }
 
-    fn add(&mut self, global_index: usize, lit: String) {
-        self.0.entry(lit.into_bytes()).or_insert(vec![]).push(global_index);
+        let mut changes = vec![];
+        let mut old_matched = vec![false; previous.pats.len()];
+        for (new_i, p) in self.pats.iter().enumerate() {
+            let key = pattern_key(p, self.negated[new_i]);
+            let old_i = old_by_key.get(&key).and_then(|olds| {
+                olds.iter().cloned().find(|&oi| !old_matched[oi])
+            });
+            match old_i {
+                Some(old_i) => {
+                    old_matched[old_i] = true;
+                    if old_i != new_i {
+                        changes.push(
+                            IndexChange::Moved { from: old_i, to: new_i });
+                    }
+                }
+                None => changes.push(IndexChange::Added(new_i)),
+            }
+        }
+        for (old_i, &matched) in old_matched.iter().enumerate() {
+            if !matched {
+                changes.push(IndexChange::Removed(old_i));
+            }
+        }
+        Ok((new_set, changes))
     }
 
-    fn is_match(&self, candidate: &Candidate) -> bool {
-        if candidate.basename.is_empty() {
-            return false;
+    /// Builds a new matcher the same way `build` does, but first collapses
+    /// any patterns added so far that are identical (same source text and
+    /// same negated flag) into a single sequence number, keeping the first
+    /// occurrence and dropping the rest.
+    ///
+    /// This is useful when patterns are collected from more than one
+    /// source (e.g. several config files) that might overlap, since
+    /// without deduplication `matches` reports one index per occurrence of
+    /// the same rule, inflating downstream match lists. The returned
+    /// `Duplicate`s record, for each dropped pattern, which sequence
+    /// number it would have had in this builder and which surviving
+    /// pattern it collided with, in the order patterns were added.
+    ///
+    /// As with `build_diff`, two glob patterns are compared by their
+    /// source text alone, not by the options they were built with, so
+    /// `Glob::new("*.rs")` and one built with `GlobBuilder::new("*.rs")
+    /// .case_insensitive(true)` are treated as duplicates of each other.
+    pub fn build_deduped(&self) -> Result<(GlobSet, Vec<Duplicate>), Error> {
+        let mut seen: HashMap<(bool, &str, bool), usize> = HashMap::new();
+        let mut deduped = GlobSetBuilder {
+            pats: vec![],
+            negated: vec![],
+            tags: vec![],
+            case_insensitive: self.case_insensitive,
+            regex_size_limit: self.regex_size_limit,
+            dfa_size_limit: self.dfa_size_limit,
+        };
+        let mut dups = vec![];
+        for (i, p) in self.pats.iter().enumerate() {
+            let key = pattern_key(p, self.negated[i]);
+            match seen.get(&key).cloned() {
+                Some(original) => {
+                    dups.push(Duplicate { original: original, duplicate: i });
+                }
+                None => {
+                    seen.insert(key, i);
+                    deduped.pats.push(p.clone());
+                    deduped.negated.push(self.negated[i]);
+                    deduped.tags.push(self.tags[i].clone());
+                }
+            }
         }
-        self.0.contains_key(&*candidate.basename)
+        let set = try!(deduped.build());
+        Ok((set, dups))
     }
 
-    #[inline(never)]
-    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
-        if candidate.basename.is_empty() {
-            return;
+    /// Builds a new matcher the same way `build` does, but instead of
+    /// stopping at the first pattern that fails to compile, attempts every
+    /// pattern and collects every failure, keyed by the sequence number it
+    /// would have had in the built set.
+    ///
+    /// This is meant for a config UI editing many patterns at once, where
+    /// reporting only the first error forces the user through a fix one,
+    /// re-submit, fix the next cycle instead of seeing everything wrong at
+    /// once. A glob added via `add`/`add_str` only needs re-checking here
+    /// when `case_insensitive` is on, since it recompiles the pattern with
+    /// case insensitivity forced on (same as `build`); a raw regex added
+    /// via `add_regex`/`add_negated_regex` is checked here for the first
+    /// time, since unlike a glob it isn't compiled until `build` runs.
+    pub fn build_validated(
+        &self,
+    ) -> Result<GlobSet, Vec<(usize, Error)>> {
+        let mut errs = vec![];
+        for (i, p) in self.pats.iter().enumerate() {
+            match *p {
+                Pattern::Glob(ref g) if self.case_insensitive => {
+                    if let Err(err) = GlobBuilder::new(g.glob())
+                        .case_insensitive(true)
+                        .build() {
+                        errs.push((i, err));
+                    }
+                }
+                Pattern::Glob(_) => {}
+                Pattern::Regex(ref r) => {
+                    if let Err(err) = new_regex(
+                        r, self.regex_size_limit, self.dfa_size_limit) {
+                        errs.push((i, err.with_glob(r).with_glob_index(i)));
+                    }
+                }
+            }
         }
-        if let Some(hits) = self.0.get(&*candidate.basename) {
-            matches.extend(hits);
+        if !errs.is_empty() {
+            return Err(errs);
         }
+        self.build().map_err(|err| vec![(err.glob_index().unwrap_or(0), err)])
     }
-}
 
-#[derive(Clone, Debug)]
-struct ExtensionStrategy(HashMap<OsString, Vec<usize>, Fnv>);
+    /// Add a new pattern to this set.
+    #[allow(dead_code)]
+    pub fn add(&mut self, pat: Glob) -> &mut GlobSetBuilder {
+        self.pats.push(Pattern::Glob(pat));
+        self.negated.push(false);
+        self.tags.push(None);
+        self
+    }
 
-impl ExtensionStrategy {
-    fn new() -> ExtensionStrategy {
-        ExtensionStrategy(HashMap::with_hasher(Fnv::default()))
+    /// Add a new pattern to this set, tagged with a caller-supplied string.
+    ///
+    /// The tag is later retrievable via `GlobSet::tag` at whatever sequence
+    /// number this pattern ends up with, e.g. for reporting which of
+    /// several tools sharing one `GlobSet` owns a matched pattern. This is
+    /// lighter than building a full `GlobMap<V>` when a single label is all
+    /// that's needed.
+    pub fn add_tagged(
+        &mut self,
+        pat: Glob,
+        tag: String,
+    ) -> &mut GlobSetBuilder {
+        self.pats.push(Pattern::Glob(pat));
+        self.negated.push(false);
+        self.tags.push(Some(tag));
+        self
     }
 
-    fn add(&mut self, global_index: usize, ext: OsString) {
-        self.0.entry(ext).or_insert(vec![]).push(global_index);
+    /// Parses `pat` with default options and adds it to this set.
+    ///
+    /// This is shorthand for `self.add(try!(Glob::new(pat)))`, which is
+    /// convenient when feeding a set from a `Vec<String>` of patterns, e.g.
+    /// read from a config file, rather than building each `Glob` by hand.
+    pub fn add_str(&mut self, pat: &str) -> Result<&mut GlobSetBuilder, Error> {
+        let glob = try!(Glob::new(pat));
+        Ok(self.add(glob))
     }
 
-    fn is_match(&self, candidate: &Candidate) -> bool {
-        if candidate.ext.is_empty() {
-            return false;
+    /// Parses `pat` with default options and adds it to this set, reporting
+    /// `ErrorKind::InvalidUtf8` if it isn't valid UTF-8, rather than lossily
+    /// converting it.
+    ///
+    /// This is for loading patterns from a source that may contain
+    /// non-UTF-8 bytes, e.g. a config file read as raw `OsStr` lines, where
+    /// silently mangling an invalid line would be worse than rejecting it.
+    /// This crate's glob parser only ever works on `&str`, so there's no
+    /// way to actually match with a non-UTF-8 pattern; this exists only to
+    /// give such a pattern a clear error instead of a lossy one.
+    pub fn add_os(
+        &mut self,
+        pat: &OsStr,
+    ) -> Result<&mut GlobSetBuilder, Error> {
+        match pat.to_str() {
+            Some(pat) => self.add_str(pat),
+            None => Err(Error::from_kind(ErrorKind::InvalidUtf8)),
         }
-        self.0.contains_key(candidate.ext)
     }
 
-    #[inline(never)]
-    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
-        if candidate.ext.is_empty() {
-            return;
-        }
-        if let Some(hits) = self.0.get(candidate.ext) {
-            matches.extend(hits);
+    /// Returns true if this builder already has a non-negated glob pattern
+    /// whose original text is exactly `pat`.
+    ///
+    /// This is meant for interactive rule editing, so a caller can check
+    /// whether an equivalent rule is already present before calling
+    /// `add_str` again, without paying for a full `build`. Comparison is by
+    /// original pattern text, the same text `build_deduped` uses to detect
+    /// duplicates, not by any compiled or normalized form, so `"src/*.rs"`
+    /// and `"src//*.rs"` are considered different even though they compile
+    /// to the same matcher.
+    pub fn contains(&self, pat: &str) -> bool {
+        self.pats.iter().enumerate().any(|(i, p)| {
+            !self.negated[i] && match *p {
+                Pattern::Glob(ref g) => g.glob() == pat,
+                Pattern::Regex(_) => false,
+            }
+        })
+    }
+
+    /// Adds every glob yielded by the given iterator to this set.
+    pub fn extend<I: IntoIterator<Item = Glob>>(&mut self, globs: I) {
+        for glob in globs {
+            self.add(glob);
         }
     }
-}
 
-#[derive(Clone, Debug)]
-struct PrefixStrategy {
-    matcher: FullAcAutomaton<Vec<u8>>,
-    map: Vec<usize>,
-    longest: usize,
-}
+    /// Add a negated pattern to this set.
+    ///
+    /// This, together with `GlobSet::matched`, is the single source of
+    /// truth for negated-pattern semantics in this crate; `parse_patterns`'s
+    /// `!` prefix is purely a convenience that routes into this method and
+    /// does not duplicate its behavior.
+    ///
+    /// A negated pattern participates in ordinary matching (via `matches`
+    /// and `is_match`) exactly like one added via `add`. The difference
+    /// only surfaces in `GlobSet::matched`: when a negated pattern is the
+    /// last (highest insertion index) pattern to match a path, the overall
+    /// decision is `Match::Whitelist` rather than `Match::Ignore`. This is
+    /// what lets later patterns in a gitignore-style rule file re-include a
+    /// path an earlier pattern excluded.
+    pub fn add_negated(&mut self, pat: Glob) -> &mut GlobSetBuilder {
+        self.pats.push(Pattern::Glob(pat));
+        self.negated.push(true);
+        self.tags.push(None);
+        self
+    }
 
-impl PrefixStrategy {
-    fn is_match(&self, candidate: &Candidate) -> bool {
-        let path = candidate.path_prefix(self.longest);
-        for m in self.matcher.find_overlapping(path) {
-            if m.start == 0 {
-                return true;
-            }
-        }
-        false
+    /// Add a raw, already-anchored regular expression to this set.
+    ///
+    /// Unlike a glob, `pat` is compiled as-is (no translation from glob
+    /// syntax), so callers that need expressiveness beyond glob syntax can
+    /// match everything in the same single pass rather than running a
+    /// separate `RegexSet` and merging index spaces by hand. The returned
+    /// sequence number behaves exactly like one returned for a glob added
+    /// via `add`.
+    pub fn add_regex(&mut self, pat: &str) -> &mut GlobSetBuilder {
+        self.pats.push(Pattern::Regex(pat.to_string()));
+        self.negated.push(false);
+        self.tags.push(None);
+        self
     }
 
-    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
-        let path = candidate.path_prefix(self.longest);
-        for m in self.matcher.find_overlapping(path) {
-            if m.start == 0 {
-                matches.push(self.map[m.pati]);
-            }
-        }
+    /// Add a negated raw, already-anchored regular expression to this set.
+    ///
+    /// This is to `add_regex` as `add_negated` is to `add`: the regex
+    /// participates in ordinary matching like any other, but if it's the
+    /// last pattern to match a path, `GlobSet::matched` reports
+    /// `Match::Whitelist` instead of `Match::Ignore`.
+    pub fn add_negated_regex(&mut self, pat: &str) -> &mut GlobSetBuilder {
+        self.pats.push(Pattern::Regex(pat.to_string()));
+        self.negated.push(true);
+        self.tags.push(None);
+        self
     }
 }
 
-#[derive(Clone, Debug)]
-struct SuffixStrategy {
-    matcher: FullAcAutomaton<Vec<u8>>,
-    map: Vec<usize>,
-    longest: usize,
+impl Default for GlobSetBuilder {
+    /// Returns a builder with no patterns added yet. Equivalent to
+    /// `GlobSetBuilder::new()`.
+    fn default() -> GlobSetBuilder {
+        GlobSetBuilder::new()
+    }
 }
 
-impl SuffixStrategy {
-    fn is_match(&self, candidate: &Candidate) -> bool {
-        let path = candidate.path_suffix(self.longest);
-        for m in self.matcher.find_overlapping(path) {
-            if m.end == path.len() {
-                return true;
+impl ::std::iter::FromIterator<Glob> for GlobSetBuilder {
+    /// Collects an iterator of `Glob`s into a builder, equivalent to
+    /// calling `extend` on a fresh `GlobSetBuilder::new()`.
+    ///
+    /// This is what makes an iterator of `&str` patterns collectible into a
+    /// `GlobSet` in one pipeline: `Iterator<Item = &str>` maps to
+    /// `Iterator<Item = Result<Glob, Error>>` via `Glob::new`, which the
+    /// standard library already knows how to collect into
+    /// `Result<GlobSetBuilder, Error>` given this impl, ready for `build`:
+    ///
+    /// ```
+    /// # fn example() -> Result<(), globset::Error> {
+    /// use globset::{Glob, GlobSetBuilder};
+    ///
+    /// let patterns = vec!["*.rs", "*.md", "*.toml"];
+    /// let builder = try!(patterns
+    ///     .into_iter()
+    ///     .map(Glob::new)
+    ///     .collect::<Result<GlobSetBuilder, _>>());
+    /// let set = try!(builder.build());
+    /// assert!(set.is_match("lib.rs"));
+    /// # Ok(()) } example().unwrap();
+    /// ```
+    fn from_iter<T: IntoIterator<Item = Glob>>(iter: T) -> GlobSetBuilder {
+        let mut builder = GlobSetBuilder::new();
+        builder.extend(iter);
+        builder
+    }
+}
+
+/// Serializes a `GlobSetBuilder` (and, by extension, a `GlobSet` built from
+/// one) as a sequence of its patterns, each tagged with whether it's a glob
+/// or a raw regex and whether it was negated. Deserializing re-runs the
+/// parser/compiler for every glob rather than trying to (de)serialize the
+/// compiled strategies a `GlobSet` uses internally.
+#[cfg(feature = "serde1")]
+mod globset_serde_impl {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::ser::SerializeSeq;
+
+    use super::{Glob, GlobSet, GlobSetBuilder, Pattern};
+
+    #[derive(Serialize, Deserialize)]
+    enum SerializedPattern {
+        Glob(Glob),
+        Regex(String),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct SerializedEntry {
+        pattern: SerializedPattern,
+        negated: bool,
+    }
+
+    impl Serialize for GlobSetBuilder {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where S: Serializer {
+            let mut seq = try!(serializer.serialize_seq(Some(self.pats.len())));
+            for (pat, &negated) in self.pats.iter().zip(&self.negated) {
+                let pattern = match *pat {
+                    Pattern::Glob(ref g) => SerializedPattern::Glob(g.clone()),
+                    Pattern::Regex(ref r) => SerializedPattern::Regex(r.clone()),
+                };
+                try!(seq.serialize_element(
+                    &SerializedEntry { pattern: pattern, negated: negated }));
             }
+            seq.end()
         }
-        false
     }
 
-    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
-        let path = candidate.path_suffix(self.longest);
-        for m in self.matcher.find_overlapping(path) {
-            if m.end == path.len() {
-                matches.push(self.map[m.pati]);
+    impl Serialize for GlobSet {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where S: Serializer {
+            let mut seq = try!(serializer.serialize_seq(Some(self.pats.len())));
+            for (pat, &negated) in self.pats.iter().zip(&self.negated) {
+                let pattern = match *pat {
+                    Pattern::Glob(ref g) => SerializedPattern::Glob(g.clone()),
+                    Pattern::Regex(ref r) => SerializedPattern::Regex(r.clone()),
+                };
+                try!(seq.serialize_element(
+                    &SerializedEntry { pattern: pattern, negated: negated }));
             }
+            seq.end()
         }
     }
-}
 
-#[derive(Clone, Debug)]
-struct RequiredExtensionStrategy(HashMap<OsString, Vec<(usize, Regex)>, Fnv>);
-
-impl RequiredExtensionStrategy {
-    fn is_match(&self, candidate: &Candidate) -> bool {
-        if candidate.ext.is_empty() {
-            return false;
-        }
-        match self.0.get(candidate.ext) {
-            None => false,
-            Some(regexes) => {
-                for &(_, ref re) in regexes {
-                    if re.is_match(&*candidate.path) {
-                        return true;
+    impl<'de> Deserialize<'de> for GlobSetBuilder {
+        fn deserialize<D>(deserializer: D) -> Result<GlobSetBuilder, D::Error>
+                where D: Deserializer<'de> {
+            let entries = try!(<Vec<SerializedEntry>>::deserialize(deserializer));
+            let mut builder = GlobSetBuilder::new();
+            for entry in entries {
+                match entry.pattern {
+                    SerializedPattern::Glob(g) => {
+                        if entry.negated {
+                            builder.add_negated(g);
+                        } else {
+                            builder.add(g);
+                        }
+                    }
+                    SerializedPattern::Regex(r) => {
+                        if entry.negated {
+                            builder.add_negated_regex(&r);
+                        } else {
+                            builder.add_regex(&r);
+                        }
                     }
                 }
-                false
             }
+            Ok(builder)
         }
     }
+}
 
-    #[inline(never)]
-    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
-        if candidate.ext.is_empty() {
-            return;
-        }
-        if let Some(regexes) = self.0.get(candidate.ext) {
-            for &(global_index, ref re) in regexes {
-                if re.is_match(&*candidate.path) {
-                    matches.push(global_index);
-                }
+/// Escapes `s` so that it can be built into a `Glob` (or added to a
+/// `GlobSetBuilder`) that matches `s` literally, regardless of whatever
+/// glob metacharacters it happens to contain, mirroring `regex::escape`.
+///
+/// Each metacharacter (`*`, `?`, `[`, `{`, `}`, `\`) is wrapped in its own
+/// single-character class, e.g. `*` becomes `[*]`, rather than backslash-escaped,
+/// since `GlobBuilder::backslash_escape` defaults to off on Windows and this
+/// needs to produce a pattern that matches literally under default options
+/// on every platform.
+///
+/// ```
+/// use globset::{escape, Glob};
+///
+/// let glob = Glob::new(&escape("a*b")).unwrap().compile_matcher();
+/// assert!(glob.is_match("a*b"));
+/// assert!(!glob.is_match("axb"));
+/// ```
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '*' | '?' | '[' | '{' | '}' | '\\' => {
+                escaped.push('[');
+                escaped.push(c);
+                escaped.push(']');
             }
+            _ => escaped.push(c),
         }
     }
+    escaped
 }
 
-#[derive(Clone, Debug)]
-struct RegexSetStrategy {
-    matcher: RegexSet,
-    map: Vec<usize>,
+/// Parses a list of pattern-file lines into a `GlobSetBuilder`.
+///
+/// This understands the multi-syntax convention used by version-control
+/// ignore files: each line may carry an inline syntax prefix, `glob:`,
+/// `rootglob:`, `path:` or `re:`, and a bare `syntax: glob` / `syntax: re`
+/// header line switches the default syntax used by subsequent prefix-less
+/// lines until it is changed again. Blank lines and `#` comments are
+/// skipped.
+///
+/// * `glob:` adds an ordinary glob that, if it contains no `/` (other than
+///   a single trailing one), is allowed to match starting at any directory
+///   depth, just as in a gitignore pattern.
+/// * `rootglob:` adds a glob anchored to the start of the path, with no
+///   implicit leading `**/` match.
+/// * `path:` adds a literal directory-prefix match: the given path, and
+///   everything beneath it.
+/// * `re:` adds an already-anchored raw regex via `GlobSetBuilder::add_regex`.
+///
+/// A line may also start with `!`, in which case the rest of the line is
+/// parsed as above but added via `GlobSetBuilder::add_negated` instead,
+/// mirroring the gitignore convention of using `!` to re-include a path
+/// excluded by an earlier pattern. This only matters to callers using
+/// `GlobSet::matched`; `GlobSet::matches` and `is_match` treat a negated
+/// pattern like any other.
+///
+/// This gives downstream tools a single entry point for turning a config
+/// file into a `GlobSet`.
+pub fn parse_patterns<I, S>(lines: I) -> Result<GlobSetBuilder, Error>
+        where S: AsRef<str>, I: IntoIterator<Item=S> {
+    let mut builder = GlobSetBuilder::new();
+    let mut default_is_regex = false;
+    for line in lines {
+        let line = line.as_ref().trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with("syntax:") {
+            match line["syntax:".len()..].trim() {
+                "glob" => default_is_regex = false,
+                "re" => default_is_regex = true,
+                _ => {}
+            }
+            continue;
+        }
+        let (negated, line) = match strip_prefix(line, "!") {
+            Some(rest) => (true, rest.trim()),
+            None => (false, line),
+        };
+        if let Some(pat) = strip_prefix(line, "rootglob:") {
+            try!(add_glob(&mut builder, pat.trim(), negated));
+        } else if let Some(pat) = strip_prefix(line, "glob:") {
+            try!(add_glob(&mut builder, &unanchored_glob(pat.trim()), negated));
+        } else if let Some(pat) = strip_prefix(line, "path:") {
+            try!(add_glob(&mut builder, &path_prefix_glob(pat.trim()), negated));
+        } else if let Some(pat) = strip_prefix(line, "re:") {
+            if negated {
+                builder.add_negated_regex(pat.trim());
+            } else {
+                builder.add_regex(pat.trim());
+            }
+        } else if default_is_regex {
+            if negated {
+                builder.add_negated_regex(line);
+            } else {
+                builder.add_regex(line);
+            }
+        } else {
+            try!(add_glob(&mut builder, &unanchored_glob(line), negated));
+        }
+    }
+    Ok(builder)
 }
 
-impl RegexSetStrategy {
-    fn is_match(&self, candidate: &Candidate) -> bool {
-        self.matcher.is_match(&*candidate.path)
+fn add_glob(
+    builder: &mut GlobSetBuilder,
+    pat: &str,
+    negated: bool,
+) -> Result<(), Error> {
+    let glob = try!(Glob::new(pat));
+    if negated {
+        builder.add_negated(glob);
+    } else {
+        builder.add(glob);
     }
+    Ok(())
+}
 
-    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
-        for i in self.matcher.matches(&*candidate.path) {
-            matches.push(self.map[i]);
-        }
+/// A key identifying a pattern for `GlobSetBuilder::build_diff`, independent
+/// of its sequence number: whether it's a raw regex, its original text, and
+/// whether it was added negated.
+fn pattern_key<'p>(p: &'p Pattern, negated: bool) -> (bool, &'p str, bool) {
+    match *p {
+        Pattern::Glob(ref g) => (false, g.glob(), negated),
+        Pattern::Regex(ref r) => (true, r.as_str(), negated),
     }
 }
 
-#[derive(Clone, Debug)]
-struct MultiStrategyBuilder {
-    literals: Vec<String>,
-    map: Vec<usize>,
-    longest: usize,
+fn strip_prefix<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    if line.starts_with(prefix) {
+        Some(&line[prefix.len()..])
+    } else {
+        None
+    }
 }
 
-impl MultiStrategyBuilder {
-    fn new() -> MultiStrategyBuilder {
-        MultiStrategyBuilder {
-            literals: vec![],
-            map: vec![],
-            longest: 0,
+/// Mimics gitignore semantics: a glob with no interior `/` may match
+/// starting at any directory depth, unless it begins with a `/`, which
+/// anchors it to the root instead (and is then stripped, since a `Glob`
+/// is always matched against a full path from the root already).
+fn unanchored_glob(pat: &str) -> String {
+    if let Some(rest) = strip_prefix(pat, "/") {
+        return rest.to_string();
+    }
+    if pat.trim_end_matches('/').contains('/') {
+        pat.to_string()
+    } else {
+        format!("**/{}", pat)
+    }
+}
+
+/// A glob matching the given directory-prefix path and everything under it.
+fn path_prefix_glob(pat: &str) -> String {
+    let pat = pat.trim_matches('/');
+    format!("{}{{,/**}}", pat)
+}
+
+/// A candidate path for matching.
+///
+/// All glob matching in this crate operates on `Candidate` values.
+/// Constructing candidates has a very small cost associated with it, so
+/// callers may find it beneficial to amortize that cost when matching a single
+/// path against multiple globs or sets of globs.
+#[derive(Clone, Debug)]
+pub struct Candidate<'a> {
+    pub(crate) path: Cow<'a, [u8]>,
+    basename: Cow<'a, [u8]>,
+    ext: &'a OsStr,
+    // The basename with its extension, if any, removed.
+    stem: Cow<'a, [u8]>,
+    // An ASCII-lowercased copy of `path`, populated only by
+    // `new_case_fold`. `None` otherwise.
+    fold: Option<Vec<u8>>,
+}
+
+impl<'a> Candidate<'a> {
+    /// Create a new candidate for matching from the given path.
+    pub fn new<P: AsRef<Path> + ?Sized>(path: &'a P) -> Candidate<'a> {
+        let path = path.as_ref();
+        let basename = file_name(path).unwrap_or(OsStr::new(""));
+        Candidate {
+            path: normalize_path(path_bytes(path)),
+            basename: os_str_bytes(basename),
+            ext: file_name_ext(basename).unwrap_or(OsStr::new("")),
+            stem: os_str_bytes(file_name_stem(basename)),
+            fold: None,
         }
     }
 
-    fn add(&mut self, global_index: usize, literal: String) {
-        if literal.len() > self.longest {
-            self.longest = literal.len();
+    /// Create a new candidate for matching from the given path, the same
+    /// way `new` does, but also eagerly compute an ASCII-lowercased copy
+    /// of its path.
+    ///
+    /// Building a candidate this way costs one extra allocation and a
+    /// linear scan over `path` up front. It pays off for a set that's
+    /// entirely case-insensitive (see `GlobSetBuilder::case_insensitive`),
+    /// where a strategy built for such a set can borrow the already-folded
+    /// bytes via `path_fold`/`basename_fold`/`ext_fold` instead of folding
+    /// the same path itself on every call.
+    pub fn new_case_fold<P: AsRef<Path> + ?Sized>(path: &'a P) -> Candidate<'a> {
+        let mut candidate = Candidate::new(path);
+        candidate.fold = Some(candidate.path.to_ascii_lowercase());
+        candidate
+    }
+
+    /// The lowercased path, if this candidate was built with
+    /// `new_case_fold`.
+    #[allow(dead_code)]
+    pub(crate) fn path_fold(&self) -> Option<&[u8]> {
+        self.fold.as_ref().map(|f| &f[..])
+    }
+
+    /// The lowercased basename, if this candidate was built with
+    /// `new_case_fold`.
+    #[allow(dead_code)]
+    pub(crate) fn basename_fold(&self) -> Option<&[u8]> {
+        self.fold.as_ref().map(|f| file_name_bytes(f))
+    }
+
+    /// The lowercased extension, if this candidate was built with
+    /// `new_case_fold` and its path has one.
+    #[allow(dead_code)]
+    pub(crate) fn ext_fold(&self) -> Option<&[u8]> {
+        self.fold.as_ref()
+            .and_then(|f| file_name_ext_bytes(file_name_bytes(f)))
+    }
+
+    /// Create a new candidate for matching from a wide (UTF-16) path, the
+    /// kind Windows APIs like `FindFirstFileW` hand back, without the
+    /// caller having to import `OsStringExt` itself.
+    ///
+    /// Building a `Candidate` still needs somewhere to own the `OsString`
+    /// `OsStringExt::from_wide` allocates, since `Candidate` only ever
+    /// borrows; `buf` is that storage, and it's overwritten with the
+    /// decoded path on every call, the same amortization `CandidateBuf`
+    /// gives non-wide callers who construct many candidates in a loop.
+    #[cfg(windows)]
+    pub fn from_wide(wide: &[u16], buf: &'a mut OsString) -> Candidate<'a> {
+        use std::os::windows::ffi::OsStringExt;
+        *buf = OsString::from_wide(wide);
+        Candidate::new(&*buf)
+    }
+
+    /// Create a new candidate for matching from a path that's already
+    /// normalized: it uses `/` as its only separator, with no trailing
+    /// separator.
+    ///
+    /// This skips the `normalize_path` scan `new` always runs, which saves
+    /// work in a hot loop when the caller can guarantee its paths are
+    /// already in that form. Passing a path that isn't actually normalized
+    /// (e.g. one containing `\` on a platform where that's not already
+    /// converted) yields a `Candidate` that silently matches incorrectly,
+    /// since the extension/basename split and the compiled regex both
+    /// assume `/` is the only separator present.
+    pub fn new_normalized<P: AsRef<Path> + ?Sized>(
+        path: &'a P,
+    ) -> Candidate<'a> {
+        let path = path.as_ref();
+        let basename = file_name(path).unwrap_or(OsStr::new(""));
+        Candidate {
+            path: path_bytes(path),
+            basename: os_str_bytes(basename),
+            ext: file_name_ext(basename).unwrap_or(OsStr::new("")),
+            stem: os_str_bytes(file_name_stem(basename)),
+            fold: None,
         }
-        self.map.push(global_index);
-        self.literals.push(literal);
     }
 
-    fn prefix(self) -> PrefixStrategy {
-        let it = self.literals.into_iter().map(|s| s.into_bytes());
-        PrefixStrategy {
-            matcher: AcAutomaton::new(it).into_full(),
-            map: self.map,
-            longest: self.longest,
+    /// Create a new candidate for matching directly from raw path bytes.
+    ///
+    /// Unlike `new`, this never goes through `Path`/`OsStr`: it fills in
+    /// the basename and extension by scanning `path` for the last `/` and
+    /// the last `.` directly, so `path` need not be valid UTF-8 or a valid
+    /// `OsStr` encoding for the current platform. If the portion of `path`
+    /// that would be the extension isn't valid UTF-8, it's treated as
+    /// having no extension (extension-based match strategies simply won't
+    /// fire for it) rather than panicking or lossily converting.
+    pub fn from_bytes(path: &'a [u8]) -> Candidate<'a> {
+        let basename = file_name_bytes(path);
+        let ext = file_name_ext_bytes(basename)
+            .and_then(|ext| str::from_utf8(ext).ok())
+            .map(OsStr::new)
+            .unwrap_or_else(|| OsStr::new(""));
+        Candidate {
+            path: normalize_path(Cow::Borrowed(path)),
+            basename: Cow::Borrowed(basename),
+            ext: ext,
+            stem: Cow::Borrowed(file_stem_bytes(basename)),
+            fold: None,
         }
     }
 
-    fn suffix(self) -> SuffixStrategy {
-        let it = self.literals.into_iter().map(|s| s.into_bytes());
-        SuffixStrategy {
-            matcher: AcAutomaton::new(it).into_full(),
-            map: self.map,
-            longest: self.longest,
+    /// The candidate's full, normalized path, as bytes.
+    pub fn path(&self) -> &[u8] {
+        &self.path
+    }
+
+    /// The candidate's basename, as bytes. Empty if the path has no
+    /// basename, e.g. `/`.
+    pub fn basename(&self) -> &[u8] {
+        &self.basename
+    }
+
+    /// The candidate's extension, following the same last-dot convention as
+    /// `pathutil::file_name_ext`. Empty if the basename has no extension.
+    pub fn ext(&self) -> &OsStr {
+        self.ext
+    }
+
+    fn path_prefix(&self, max: usize) -> &[u8] {
+        if self.path.len() <= max {
+            &*self.path
+        } else {
+            &self.path[..max]
         }
     }
 
-    fn regex_set(self) -> Result<RegexSetStrategy, Error> {
-        Ok(RegexSetStrategy {
-            matcher: try!(new_regex_set(self.literals)),
-            map: self.map,
-        })
+    fn path_suffix(&self, max: usize) -> &[u8] {
+        if self.path.len() <= max {
+            &*self.path
+        } else {
+            &self.path[self.path.len() - max..]
+        }
+    }
+}
+
+/// A reusable buffer that amortizes the allocation `Candidate::new` does to
+/// normalize a path, for callers that construct many candidates in a tight
+/// loop, e.g. a directory walk.
+///
+/// Unlike `Candidate`, which borrows from the path it's given, a
+/// `CandidateBuf` owns its normalized path bytes. `reset` reuses that
+/// storage's capacity for a new path instead of allocating a fresh buffer
+/// every time, and `as_candidate` produces a `Candidate` borrowing from it
+/// for actual matching.
+#[derive(Clone, Debug, Default)]
+pub struct CandidateBuf {
+    path: Vec<u8>,
+    basename_start: usize,
+    ext: Option<(usize, usize)>,
+}
+
+impl CandidateBuf {
+    /// Create a new, empty candidate buffer.
+    pub fn new() -> CandidateBuf {
+        CandidateBuf { path: vec![], basename_start: 0, ext: None }
+    }
+
+    /// Resets this buffer to the given path, reusing its internal storage.
+    pub fn reset<P: AsRef<Path> + ?Sized>(&mut self, path: &P) {
+        let bytes = normalize_path(path_bytes(path.as_ref()));
+        self.path.clear();
+        self.path.extend_from_slice(&bytes);
+        self.basename_start = match self.path.iter().rposition(|&b| b == b'/') {
+            Some(i) => i + 1,
+            None => 0,
+        };
+        self.ext = file_name_ext_bytes(&self.path[self.basename_start..])
+            .map(|ext| (self.path.len() - ext.len(), self.path.len()));
+    }
+
+    /// Borrows this buffer as a `Candidate` for matching.
+    pub fn as_candidate(&self) -> Candidate {
+        let ext = self.ext
+            .and_then(|(s, e)| str::from_utf8(&self.path[s..e]).ok())
+            .map(OsStr::new)
+            .unwrap_or_else(|| OsStr::new(""));
+        Candidate {
+            path: Cow::Borrowed(&self.path),
+            basename: Cow::Borrowed(&self.path[self.basename_start..]),
+            ext: ext,
+            stem: Cow::Borrowed(
+                file_stem_bytes(&self.path[self.basename_start..])),
+            fold: None,
+        }
+    }
+}
+
+/// Size and complexity metrics for a compiled `GlobSet`. See `GlobSet::stats`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GlobSetStats {
+    /// Number of patterns matched via an exact literal lookup.
+    pub literals: usize,
+    /// Number of patterns matched via an exact basename literal lookup.
+    pub basename_literals: usize,
+    /// Number of patterns matched via a `*.ext` extension lookup.
+    pub extensions: usize,
+    /// Number of patterns matched via a literal-prefix automaton.
+    pub prefixes: usize,
+    /// Number of patterns matched via a literal-suffix automaton.
+    pub suffixes: usize,
+    /// Number of patterns matched via an extension-scoped regex.
+    pub required_extensions: usize,
+    /// Number of patterns matched via the general-purpose `RegexSet`.
+    pub regexes: usize,
+    /// A rough estimate, in bytes, of the space taken up by this set's
+    /// compiled regex programs and literal automata. See `GlobSet::stats`.
+    pub approx_bytes: usize,
+}
+
+/// Identifies which internal strategy produced a match, for diagnosing why
+/// a `GlobSet` chose a particular fast path (or fell back to a full regex)
+/// for a given pattern. See `GlobSet::explain`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MatchSource {
+    /// The pattern is an exact literal path, matched via a lookup table.
+    Literal,
+    /// The pattern is an exact literal basename, matched via a lookup
+    /// table.
+    BasenameLiteral,
+    /// The pattern is of the form `*.ext`, matched via a lookup table keyed
+    /// on file extension.
+    Extension,
+    /// The pattern has a literal prefix, matched via an Aho-Corasick
+    /// automaton.
+    Prefix,
+    /// The pattern has a literal suffix, matched via an Aho-Corasick
+    /// automaton.
+    Suffix,
+    /// The pattern requires a specific extension but isn't a bare `*.ext`,
+    /// matched via a regex scoped to that extension.
+    RequiredExtension,
+    /// The pattern didn't fit any specialized strategy, matched via a
+    /// `RegexSet`.
+    Regex,
+}
+
+impl MatchSource {
+    fn of(strat: &GlobSetMatchStrategy) -> MatchSource {
+        match *strat {
+            GlobSetMatchStrategy::Literal(_) => MatchSource::Literal,
+            GlobSetMatchStrategy::BasenameLiteral(_) => {
+                MatchSource::BasenameLiteral
+            }
+            GlobSetMatchStrategy::Extension(_) => MatchSource::Extension,
+            GlobSetMatchStrategy::Prefix(_) => MatchSource::Prefix,
+            GlobSetMatchStrategy::Suffix(_) => MatchSource::Suffix,
+            GlobSetMatchStrategy::RequiredExtension(_) => {
+                MatchSource::RequiredExtension
+            }
+            GlobSetMatchStrategy::Regex(_) => MatchSource::Regex,
+        }
     }
 }
 
 #[derive(Clone, Debug)]
-struct RequiredExtensionStrategyBuilder(
-    HashMap<OsString, Vec<(usize, String)>>,
-);
+enum GlobSetMatchStrategy {
+    Literal(LiteralStrategy),
+    BasenameLiteral(BasenameLiteralStrategy),
+    Extension(ExtensionStrategy),
+    Prefix(PrefixStrategy),
+    Suffix(SuffixStrategy),
+    RequiredExtension(RequiredExtensionStrategy),
+    Regex(RegexSetStrategy),
+}
 
-impl RequiredExtensionStrategyBuilder {
-    fn new() -> RequiredExtensionStrategyBuilder {
-        RequiredExtensionStrategyBuilder(HashMap::new())
+impl GlobSetMatchStrategy {
+    fn is_match(&self, candidate: &Candidate) -> bool {
+        use self::GlobSetMatchStrategy::*;
+        match *self {
+            Literal(ref s) => s.is_match(candidate),
+            BasenameLiteral(ref s) => s.is_match(candidate),
+            Extension(ref s) => s.is_match(candidate),
+            Prefix(ref s) => s.is_match(candidate),
+            Suffix(ref s) => s.is_match(candidate),
+            RequiredExtension(ref s) => s.is_match(candidate),
+            Regex(ref s) => s.is_match(candidate),
+        }
     }
 
-    fn add(&mut self, global_index: usize, ext: OsString, regex: String) {
-        self.0.entry(ext).or_insert(vec![]).push((global_index, regex));
+    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
+        use self::GlobSetMatchStrategy::*;
+        match *self {
+            Literal(ref s) => s.matches_into(candidate, matches),
+            BasenameLiteral(ref s) => s.matches_into(candidate, matches),
+            Extension(ref s) => s.matches_into(candidate, matches),
+            Prefix(ref s) => s.matches_into(candidate, matches),
+            Suffix(ref s) => s.matches_into(candidate, matches),
+            RequiredExtension(ref s) => s.matches_into(candidate, matches),
+            Regex(ref s) => s.matches_into(candidate, matches),
+        }
     }
 
-    fn build(self) -> Result<RequiredExtensionStrategy, Error> {
-        let mut exts = HashMap::with_hasher(Fnv::default());
-        for (ext, regexes) in self.0.into_iter() {
-            exts.insert(ext.clone(), vec![]);
-            for (global_index, regex) in regexes {
-                let compiled = try!(new_regex(&regex));
-                exts.get_mut(&ext).unwrap().push((global_index, compiled));
+    fn for_each_match(&self, candidate: &Candidate, f: &mut FnMut(usize)) {
+        use self::GlobSetMatchStrategy::*;
+        match *self {
+            Literal(ref s) => s.for_each_match(candidate, f),
+            BasenameLiteral(ref s) => s.for_each_match(candidate, f),
+            Extension(ref s) => s.for_each_match(candidate, f),
+            Prefix(ref s) => s.for_each_match(candidate, f),
+            Suffix(ref s) => s.for_each_match(candidate, f),
+            RequiredExtension(ref s) => s.for_each_match(candidate, f),
+            Regex(ref s) => s.for_each_match(candidate, f),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct LiteralStrategy(BTreeMap<Vec<u8>, Vec<usize>>);
+
+impl LiteralStrategy {
+    fn new() -> LiteralStrategy {
+        LiteralStrategy(BTreeMap::new())
+    }
+
+    fn add(&mut self, global_index: usize, lit: String) {
+        self.0.entry(lit.into_bytes()).or_insert(vec![]).push(global_index);
+    }
+
+    fn is_match(&self, candidate: &Candidate) -> bool {
+        self.0.contains_key(&*candidate.path)
+    }
+
+    #[inline(never)]
+    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
+        if let Some(hits) = self.0.get(&*candidate.path) {
+            matches.extend(hits);
+        }
+    }
+
+    #[inline(never)]
+    fn for_each_match(&self, candidate: &Candidate, f: &mut FnMut(usize)) {
+        if let Some(hits) = self.0.get(&*candidate.path) {
+            for &i in hits {
+                f(i);
             }
         }
-        Ok(RequiredExtensionStrategy(exts))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::GlobSetBuilder;
-    use glob::Glob;
+#[derive(Clone, Debug)]
+struct BasenameLiteralStrategy(BTreeMap<Vec<u8>, Vec<usize>>);
 
-    #[test]
-    fn set_works() {
-        let mut builder = GlobSetBuilder::new();
-        builder.add(Glob::new("src/**/*.rs").unwrap());
-        builder.add(Glob::new("*.c").unwrap());
-        builder.add(Glob::new("src/lib.rs").unwrap());
-        let set = builder.build().unwrap();
+impl BasenameLiteralStrategy {
+    fn new() -> BasenameLiteralStrategy {
+        BasenameLiteralStrategy(BTreeMap::new())
+    }
 
-        assert!(set.is_match("foo.c"));
-        assert!(set.is_match("src/foo.c"));
-        assert!(!set.is_match("foo.rs"));
-        assert!(!set.is_match("tests/foo.rs"));
-        assert!(set.is_match("src/foo.rs"));
-        assert!(set.is_match("src/grep/src/main.rs"));
+    fn add(&mut self, global_index: usize, lit: String) {
+        self.0.entry(lit.into_bytes()).or_insert(vec![]).push(global_index);
+    }
 
-        let matches = set.matches("src/lib.rs");
-        assert_eq!(2, matches.len());
-        assert_eq!(0, matches[0]);
-        assert_eq!(2, matches[1]);
+    fn is_match(&self, candidate: &Candidate) -> bool {
+        if candidate.basename.is_empty() {
+            return false;
+        }
+        self.0.contains_key(&*candidate.basename)
     }
 
-    #[test]
-    fn empty_set_works() {
-        let set = GlobSetBuilder::new().build().unwrap();
-        assert!(!set.is_match(""));
-        assert!(!set.is_match("a"));
+    #[inline(never)]
+    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
+        if candidate.basename.is_empty() {
+            return;
+        }
+        if let Some(hits) = self.0.get(&*candidate.basename) {
+            matches.extend(hits);
+        }
+    }
+
+    #[inline(never)]
+    fn for_each_match(&self, candidate: &Candidate, f: &mut FnMut(usize)) {
+        if candidate.basename.is_empty() {
+            return;
+        }
+        if let Some(hits) = self.0.get(&*candidate.basename) {
+            for &i in hits {
+                f(i);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ExtensionStrategy(HashMap<OsString, Vec<usize>, Fnv>);
+
+impl ExtensionStrategy {
+    fn new() -> ExtensionStrategy {
+        ExtensionStrategy(HashMap::with_hasher(Fnv::default()))
+    }
+
+    fn add(&mut self, global_index: usize, ext: OsString) {
+        self.0.entry(ext).or_insert(vec![]).push(global_index);
+    }
+
+    fn is_match(&self, candidate: &Candidate) -> bool {
+        if candidate.ext.is_empty() {
+            return false;
+        }
+        self.0.contains_key(candidate.ext)
+    }
+
+    #[inline(never)]
+    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
+        if candidate.ext.is_empty() {
+            return;
+        }
+        if let Some(hits) = self.0.get(candidate.ext) {
+            matches.extend(hits);
+        }
+    }
+
+    #[inline(never)]
+    fn for_each_match(&self, candidate: &Candidate, f: &mut FnMut(usize)) {
+        if candidate.ext.is_empty() {
+            return;
+        }
+        if let Some(hits) = self.0.get(candidate.ext) {
+            for &i in hits {
+                f(i);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PrefixStrategy {
+    matcher: FullAcAutomaton<Vec<u8>>,
+    map: Vec<usize>,
+    longest: usize,
+}
+
+impl PrefixStrategy {
+    fn is_match(&self, candidate: &Candidate) -> bool {
+        let path = candidate.path_prefix(self.longest);
+        for m in self.matcher.find_overlapping(path) {
+            if m.start == 0 {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
+        let path = candidate.path_prefix(self.longest);
+        for m in self.matcher.find_overlapping(path) {
+            if m.start == 0 {
+                matches.push(self.map[m.pati]);
+            }
+        }
+    }
+
+    fn for_each_match(&self, candidate: &Candidate, f: &mut FnMut(usize)) {
+        let path = candidate.path_prefix(self.longest);
+        for m in self.matcher.find_overlapping(path) {
+            if m.start == 0 {
+                f(self.map[m.pati]);
+            }
+        }
+    }
+}
+
+/// Unlike `PrefixStrategy`, whose literals are searched forward and
+/// checked for `m.start == 0`, this strategy's `matcher` is built over
+/// each literal's bytes *reversed*, and searches the candidate's suffix
+/// reversed too, checking `m.start == 0` there instead of `m.end ==
+/// path.len()`. Matching at the front of a reversed string is exactly
+/// matching at the back of the original one; anchoring the search that
+/// way lets the automaton reject a non-matching suffix as soon as its
+/// last byte fails to continue any literal, rather than only learning
+/// that after finding a match elsewhere in the path and checking its end
+/// position.
+#[derive(Clone, Debug)]
+struct SuffixStrategy {
+    matcher: FullAcAutomaton<Vec<u8>>,
+    map: Vec<usize>,
+    longest: usize,
+}
+
+impl SuffixStrategy {
+    fn is_match(&self, candidate: &Candidate) -> bool {
+        let path = reversed(candidate.path_suffix(self.longest));
+        for m in self.matcher.find_overlapping(&path) {
+            if m.start == 0 {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
+        let path = reversed(candidate.path_suffix(self.longest));
+        for m in self.matcher.find_overlapping(&path) {
+            if m.start == 0 {
+                matches.push(self.map[m.pati]);
+            }
+        }
+    }
+
+    fn for_each_match(&self, candidate: &Candidate, f: &mut FnMut(usize)) {
+        let path = reversed(candidate.path_suffix(self.longest));
+        for m in self.matcher.find_overlapping(&path) {
+            if m.start == 0 {
+                f(self.map[m.pati]);
+            }
+        }
+    }
+}
+
+/// Returns `bytes` with its byte order reversed, for `SuffixStrategy`'s
+/// reverse-anchored search.
+fn reversed(bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    bytes.reverse();
+    bytes
+}
+
+#[derive(Clone, Debug)]
+struct RequiredExtensionStrategy(HashMap<OsString, Vec<(usize, Regex)>, Fnv>);
+
+impl RequiredExtensionStrategy {
+    fn is_match(&self, candidate: &Candidate) -> bool {
+        if candidate.ext.is_empty() {
+            return false;
+        }
+        match self.0.get(candidate.ext) {
+            None => false,
+            Some(regexes) => {
+                for &(_, ref re) in regexes {
+                    if re.is_match(&*candidate.path) {
+                        return true;
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    #[inline(never)]
+    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
+        if candidate.ext.is_empty() {
+            return;
+        }
+        if let Some(regexes) = self.0.get(candidate.ext) {
+            for &(global_index, ref re) in regexes {
+                if re.is_match(&*candidate.path) {
+                    matches.push(global_index);
+                }
+            }
+        }
+    }
+
+    #[inline(never)]
+    fn for_each_match(&self, candidate: &Candidate, f: &mut FnMut(usize)) {
+        if candidate.ext.is_empty() {
+            return;
+        }
+        if let Some(regexes) = self.0.get(candidate.ext) {
+            for &(global_index, ref re) in regexes {
+                if re.is_match(&*candidate.path) {
+                    f(global_index);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct RegexSetStrategy {
+    matcher: RegexSet,
+    map: Vec<usize>,
+    // A cheap "does the candidate contain anything a pattern in this set
+    // requires" pre-filter, built from `required_literal`. Only present
+    // when every pattern in the set yielded a required literal, since
+    // otherwise the un-filterable pattern could still match and the
+    // pre-filter could never be trusted to reject a candidate.
+    prefilter: Option<FullAcAutomaton<Vec<u8>>>,
+}
+
+impl RegexSetStrategy {
+    fn is_match(&self, candidate: &Candidate) -> bool {
+        if !self.may_match(candidate) {
+            return false;
+        }
+        self.matcher.is_match(&*candidate.path)
+    }
+
+    fn matches_into(&self, candidate: &Candidate, matches: &mut Vec<usize>) {
+        if !self.may_match(candidate) {
+            return;
+        }
+        for i in self.matcher.matches(&*candidate.path) {
+            matches.push(self.map[i]);
+        }
+    }
+
+    fn for_each_match(&self, candidate: &Candidate, f: &mut FnMut(usize)) {
+        if !self.may_match(candidate) {
+            return;
+        }
+        for i in self.matcher.matches(&*candidate.path) {
+            f(self.map[i]);
+        }
+    }
+
+    /// Whether the candidate contains at least one literal required by
+    /// some pattern in this set. Always `true` when there's no prefilter,
+    /// since it can't be trusted to reject anything in that case.
+    fn may_match(&self, candidate: &Candidate) -> bool {
+        match self.prefilter {
+            None => true,
+            Some(ref ac) => ac.find(&*candidate.path).next().is_some(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct MultiStrategyBuilder {
+    literals: Vec<Rc<str>>,
+    // A set this large tends to repeat the same handful of extensions and
+    // directory names (`.rs`, `target/`, `node_modules/`, ...) across
+    // thousands of patterns; interning collapses each distinct string down
+    // to one `Rc<str>` allocation shared by every literal equal to it,
+    // rather than storing a separate `String` per occurrence.
+    interned: HashMap<Rc<str>, Rc<str>>,
+    map: Vec<usize>,
+    longest: usize,
+}
+
+impl MultiStrategyBuilder {
+    fn new() -> MultiStrategyBuilder {
+        MultiStrategyBuilder {
+            literals: vec![],
+            interned: HashMap::new(),
+            map: vec![],
+            longest: 0,
+        }
+    }
+
+    fn add(&mut self, global_index: usize, literal: String) {
+        if literal.len() > self.longest {
+            self.longest = literal.len();
+        }
+        self.map.push(global_index);
+        let literal: Rc<str> = Rc::from(literal);
+        let interned = self.interned
+            .entry(literal.clone())
+            .or_insert(literal)
+            .clone();
+        self.literals.push(interned);
+    }
+
+    fn prefix(self) -> PrefixStrategy {
+        let it = self.literals.iter().map(|s| s.as_bytes().to_vec());
+        PrefixStrategy {
+            matcher: AcAutomaton::new(it).into_full(),
+            map: self.map,
+            longest: self.longest,
+        }
+    }
+
+    fn suffix(self) -> SuffixStrategy {
+        let it = self.literals.iter().map(|s| reversed(s.as_bytes()));
+        SuffixStrategy {
+            matcher: AcAutomaton::new(it).into_full(),
+            map: self.map,
+            longest: self.longest,
+        }
+    }
+
+    fn regex_set(
+        self,
+        size_limit: usize,
+        dfa_size_limit: usize,
+    ) -> Result<RegexSetStrategy, Error> {
+        // Compile each pattern on its own first, so that a failure can be
+        // attributed to the pattern (and its sequence number) that caused
+        // it; `RegexSet::new` alone gives no such attribution.
+        for (rank, pat) in self.literals.iter().enumerate() {
+            if let Err(err) = new_regex(pat, size_limit, dfa_size_limit) {
+                return Err(err.with_glob_index(self.map[rank]).with_glob(pat));
+            }
+        }
+        let required: Option<Vec<String>> =
+            self.literals.iter().map(|p| required_literal(p)).collect();
+        let prefilter = required.map(|lits| {
+            AcAutomaton::new(lits.into_iter().map(String::into_bytes)).into_full()
+        });
+        Ok(RegexSetStrategy {
+            matcher: try!(
+                new_regex_set(self.literals, size_limit, dfa_size_limit)),
+            map: self.map,
+            prefilter: prefilter,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct RequiredExtensionStrategyBuilder(
+    HashMap<OsString, Vec<(usize, String)>>,
+);
+
+impl RequiredExtensionStrategyBuilder {
+    fn new() -> RequiredExtensionStrategyBuilder {
+        RequiredExtensionStrategyBuilder(HashMap::new())
+    }
+
+    fn add(&mut self, global_index: usize, ext: OsString, regex: String) {
+        self.0.entry(ext).or_insert(vec![]).push((global_index, regex));
+    }
+
+    fn build(
+        self,
+        size_limit: usize,
+        dfa_size_limit: usize,
+    ) -> Result<RequiredExtensionStrategy, Error> {
+        let mut exts = HashMap::with_hasher(Fnv::default());
+        for (ext, regexes) in self.0.into_iter() {
+            exts.insert(ext.clone(), vec![]);
+            for (global_index, regex) in regexes {
+                let compiled = try!(
+                    new_regex(&regex, size_limit, dfa_size_limit)
+                        .map_err(|err| {
+                            err.with_glob_index(global_index)
+                                .with_glob(&regex)
+                        })
+                );
+                exts.get_mut(&ext).unwrap().push((global_index, compiled));
+            }
+        }
+        Ok(RequiredExtensionStrategy(exts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::env;
+    use std::ffi::{OsStr, OsString};
+    use std::path::Path;
+
+    use super::{
+        escape, parse_patterns, Candidate, Duplicate, ErrorKind, GlobSet,
+        GlobSetBuilder, IndexChange, Match, MatchSource, MultiStrategyBuilder,
+    };
+    use glob::Glob;
+
+    #[test]
+    fn set_works() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/**/*.rs").unwrap());
+        builder.add(Glob::new("*.c").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert!(set.is_match("foo.c"));
+        assert!(set.is_match("src/foo.c"));
+        assert!(!set.is_match("foo.rs"));
+        assert!(!set.is_match("tests/foo.rs"));
+        assert!(set.is_match("src/foo.rs"));
+        assert!(set.is_match("src/grep/src/main.rs"));
+
+        let matches = set.matches("src/lib.rs");
+        assert_eq!(2, matches.len());
+        assert_eq!(0, matches[0]);
+        assert_eq!(2, matches[1]);
+    }
+
+    #[test]
+    fn describe_lists_each_pattern_with_its_strategy() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("**/*.rs").unwrap());
+        builder.add(Glob::new("src/**/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let described = set.describe();
+        assert!(described.contains("[0] **/*.rs (Extension)"));
+        assert!(described.contains("src/**/*.rs"));
+    }
+
+    #[test]
+    fn tag_reports_the_owner_of_a_matched_index() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add_tagged(Glob::new("*.rs").unwrap(), "lint".to_string());
+        builder.add(Glob::new("*.md").unwrap());
+        let set = builder.build().unwrap();
+
+        let matches = set.matches("main.rs");
+        assert_eq!(matches, vec![0]);
+        assert_eq!(set.tag(matches[0]), Some("lint"));
+        assert_eq!(set.tag(1), None);
+    }
+
+    #[test]
+    fn interesting_extensions_unions_extension_and_required_extension_strategies() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        let set = builder.build().unwrap();
+
+        let exts = set.interesting_extensions().unwrap();
+        let want: HashSet<OsString> = vec![
+            OsString::from("rs"), OsString::from("md"),
+        ].into_iter().collect();
+        assert_eq!(exts, want);
+    }
+
+    #[test]
+    fn interesting_extensions_returns_none_when_a_pattern_could_match_anything() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("**").unwrap());
+        let set = builder.build().unwrap();
+
+        assert!(set.interesting_extensions().is_none());
+    }
+
+    #[test]
+    fn matches_returns_indices_in_ascending_order_across_differing_strategies() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add_regex("^a\\.rs$");
+        builder.add(Glob::new("a.rs").unwrap());
+        builder.add(Glob::new("**/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.matches("a.rs"), vec![0, 1, 2]);
+        assert_eq!(set.matches_in_insertion_order("a.rs"), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn suffix_strategy_matches_via_its_reverse_anchored_search() {
+        let mut builder = MultiStrategyBuilder::new();
+        builder.add(0, ".rs".to_string());
+        builder.add(1, ".c".to_string());
+        let strategy = builder.suffix();
+
+        assert!(strategy.is_match(&Candidate::new("src/lib.rs")));
+        assert!(strategy.is_match(&Candidate::new("src/lib.c")));
+        assert!(!strategy.is_match(&Candidate::new("src/lib.rsx")));
+        assert!(!strategy.is_match(&Candidate::new("src/lib.txt")));
+
+        let mut matches = vec![];
+        strategy.matches_into(&Candidate::new("src/lib.rs"), &mut matches);
+        assert_eq!(matches, vec![0]);
+    }
+
+    #[test]
+    fn multi_strategy_builder_interns_repeated_literals() {
+        use std::rc::Rc;
+
+        let mut builder = MultiStrategyBuilder::new();
+        builder.add(0, "target".to_string());
+        builder.add(1, "target".to_string());
+        builder.add(2, "node_modules".to_string());
+
+        // Two occurrences of an equal literal share one allocation...
+        assert!(Rc::ptr_eq(&builder.literals[0], &builder.literals[1]));
+        // ...but a distinct literal gets its own.
+        assert!(!Rc::ptr_eq(&builder.literals[0], &builder.literals[2]));
+    }
+
+    #[test]
+    fn empty_set_works() {
+        let set = GlobSetBuilder::new().build().unwrap();
+        assert!(!set.is_match(""));
+        assert!(!set.is_match("a"));
+    }
+
+    #[test]
+    fn raw_regex_mixes_with_globs() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add_regex(r"^src/.*\.c$");
+        let set = builder.build().unwrap();
+
+        assert!(set.is_match("foo.rs"));
+        assert!(set.is_match("src/foo.c"));
+        assert!(!set.is_match("other/foo.c"));
+
+        let matches = set.matches("src/foo.c");
+        assert_eq!(matches, vec![1]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn add_os_reports_invalid_utf8_instead_of_converting_lossily() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut builder = GlobSetBuilder::new();
+        let invalid = OsStr::from_bytes(&[0x66, 0x6f, 0x6f, 0xFF]);
+        let err = builder.add_os(invalid).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidUtf8);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn add_os_adds_a_valid_utf8_pattern_normally() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add_os(OsStr::new("*.rs")).unwrap();
+        let set = builder.build().unwrap();
+        assert!(set.is_match("main.rs"));
+    }
+
+    #[test]
+    fn parse_patterns_understands_prefixes_and_syntax_header() {
+        let lines = vec![
+            "# a comment",
+            "",
+            "*.log",
+            "rootglob:build/*.o",
+            "syntax: re",
+            r"^tmp/.*\.bak$",
+            "syntax: glob",
+            "glob:*.tmp",
+        ];
+        let set = parse_patterns(lines).unwrap().build().unwrap();
+
+        assert!(set.is_match("foo.log"));
+        assert!(set.is_match("src/foo.log"));
+        assert!(set.is_match("build/foo.o"));
+        assert!(!set.is_match("src/build/foo.o"));
+        assert!(set.is_match("tmp/foo.bak"));
+        assert!(set.is_match("src/foo.tmp"));
+    }
+
+    #[test]
+    fn matched_is_last_match_wins() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.log").unwrap());
+        builder.add_negated(Glob::new("keep.log").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.matched("foo.log"), Match::Ignore);
+        assert_eq!(set.matched("keep.log"), Match::Whitelist);
+        assert_eq!(set.matched("foo.rs"), Match::None);
+    }
+
+    #[cfg(feature = "serde1")]
+    #[test]
+    fn globset_serde_round_trip() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add_negated(Glob::new("keep.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let encoded = ::serde_json::to_string(&builder).unwrap();
+        let decoded: GlobSet = ::serde_json::from_str(&encoded).unwrap();
+
+        assert_eq!(set.matches("foo.rs"), decoded.matches("foo.rs"));
+        assert_eq!(decoded.matched("keep.rs"), Match::Whitelist);
+    }
+
+    #[cfg(feature = "serde1")]
+    #[test]
+    fn compiled_glob_set_serializes_and_round_trips() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        builder.add_negated(Glob::new("keep.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        // Serializing the compiled `GlobSet` directly (not the builder that
+        // produced it) still round-trips through the original patterns,
+        // since a `GlobSet` retains them for exactly this purpose.
+        let encoded = ::serde_json::to_string(&set).unwrap();
+        let decoded: GlobSet = ::serde_json::from_str(&encoded).unwrap();
+
+        assert_eq!(set.len(), decoded.len());
+        assert_eq!(set.matches("foo.rs"), decoded.matches("foo.rs"));
+        assert_eq!(set.matches("foo.md"), decoded.matches("foo.md"));
+        assert_eq!(decoded.matched("keep.rs"), Match::Whitelist);
+    }
+
+    #[test]
+    fn parse_patterns_negation_applies_to_regex_lines() {
+        let lines = vec![
+            "*.log",
+            "syntax: re",
+            r"!^keep/.*\.log$",
+            "re:^also-keep\\.log$",
+            "!re:^also-keep\\.log$",
+        ];
+        let set = parse_patterns(lines).unwrap().build().unwrap();
+
+        assert_eq!(set.matched("foo.log"), Match::Ignore);
+        assert_eq!(set.matched("keep/a.log"), Match::Whitelist);
+        // The last matching pattern for "also-keep.log" is the negated
+        // `!re:` line, so it should win over the earlier non-negated one.
+        assert_eq!(set.matched("also-keep.log"), Match::Whitelist);
+    }
+
+    #[test]
+    fn from_gitignore_lines_builds_a_set_from_gitignore_syntax() {
+        let lines = vec![
+            "# a comment, and a blank line follow",
+            "",
+            "*.log",
+            "!keep.log",
+        ];
+        let set = GlobSet::from_gitignore_lines(lines).unwrap();
+
+        assert!(set.is_match("a.log"));
+        assert!(set.is_match("src/a.log"));
+        assert!(!set.is_match("a.txt"));
+        assert_eq!(set.matched("a.log"), Match::Ignore);
+        assert_eq!(set.matched("keep.log"), Match::Whitelist);
+    }
+
+    #[test]
+    fn from_gitignore_lines_anchors_a_leading_slash_to_the_root() {
+        let set = GlobSet::from_gitignore_lines(vec!["/foo"]).unwrap();
+
+        assert!(set.is_match("foo"));
+        assert!(!set.is_match("a/foo"));
+    }
+
+    #[test]
+    fn build_diff_reports_added_removed_and_moved_patterns() {
+        let mut before = GlobSetBuilder::new();
+        before.add(Glob::new("*.rs").unwrap());
+        before.add(Glob::new("*.md").unwrap());
+        let previous = before.build().unwrap();
+
+        // Drop `*.md`, keep `*.rs` (now at a different index), and add
+        // `*.txt`.
+        let mut after = GlobSetBuilder::new();
+        after.add(Glob::new("*.txt").unwrap());
+        after.add(Glob::new("*.rs").unwrap());
+
+        let (set, mut changes) = after.build_diff(&previous).unwrap();
+        changes.sort_by_key(|c| match *c {
+            IndexChange::Added(i) => (0, i),
+            IndexChange::Removed(i) => (1, i),
+            IndexChange::Moved { to, .. } => (2, to),
+        });
+        assert_eq!(
+            changes,
+            vec![
+                IndexChange::Added(0),
+                IndexChange::Removed(1),
+                IndexChange::Moved { from: 0, to: 1 },
+            ]);
+        assert!(set.is_match("foo.txt"));
+        assert!(set.is_match("foo.rs"));
+    }
+
+    #[test]
+    fn build_deduped_collapses_identical_patterns_into_one_index() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        builder.add(Glob::new("*.rs").unwrap());
+
+        let (set, dups) = builder.build_deduped().unwrap();
+
+        assert_eq!(set.len(), 2);
+        assert_eq!(dups, vec![Duplicate { original: 0, duplicate: 2 }]);
+        assert_eq!(set.matches("foo.rs"), vec![0]);
+    }
+
+    #[test]
+    fn from_iter_collects_globs_into_a_set() {
+        let globs = vec![
+            Glob::new("*.rs").unwrap(),
+            Glob::new("*.md").unwrap(),
+            Glob::new("*.toml").unwrap(),
+        ];
+        let set = GlobSet::from_iter(globs).unwrap();
+
+        assert!(set.is_match("lib.rs"));
+        assert!(set.is_match("README.md"));
+        assert!(set.is_match("Cargo.toml"));
+        assert!(!set.is_match("lib.c"));
+    }
+
+    #[test]
+    fn matched_tracker_reports_a_glob_that_never_matched() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        builder.add(Glob::new("*.toml").unwrap());
+        let set = builder.build().unwrap();
+
+        let mut tracker = set.matched_tracker();
+        assert!(tracker.is_match("lib.rs"));
+        assert!(tracker.is_match("main.rs"));
+        assert!(tracker.is_match("README.md"));
+        assert!(!tracker.is_match("lib.c"));
+
+        assert_eq!(tracker.unmatched(), vec![2]);
+    }
+
+    #[test]
+    fn escape_produces_a_glob_matching_only_the_exact_string() {
+        let glob = Glob::new(&escape("a*b")).unwrap().compile_matcher();
+        assert!(glob.is_match("a*b"));
+        assert!(!glob.is_match("axb"));
+        assert!(!glob.is_match("ab"));
+
+        let glob = Glob::new(&escape("weird{c}[d]?e")).unwrap().compile_matcher();
+        assert!(glob.is_match("weird{c}[d]?e"));
+    }
+
+    #[test]
+    fn filter_reader_writes_only_the_matching_lines() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let input = b"foo.rs\r\nbar.txt\nbaz.rs\n";
+        let mut output = vec![];
+        set.filter_reader(&input[..], &mut output).unwrap();
+
+        assert_eq!(output, b"foo.rs\nbaz.rs\n");
+    }
+
+    #[test]
+    fn matches_unsorted_agrees_with_matches_once_sorted_and_deduped() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let mut got = vec![];
+        set.matches_unsorted("src/lib.rs", &mut got);
+        got.sort();
+        got.dedup();
+        assert_eq!(got, set.matches("src/lib.rs"));
+    }
+
+    #[test]
+    fn for_each_match_reports_every_matching_index() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let mut hits = vec![];
+        set.for_each_match("src/lib.rs", &mut |i| hits.push(i));
+        hits.sort();
+        assert_eq!(hits, vec![0, 1]);
+
+        let mut hits = vec![];
+        set.for_each_match("foo.c", &mut |i| hits.push(i));
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn build_validated_reports_every_malformed_pattern() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add_regex("[abc");
+        builder.add(Glob::new("*.md").unwrap());
+        builder.add_regex("(unclosed");
+
+        let errs = match builder.build_validated() {
+            Ok(_) => panic!("expected build_validated to fail"),
+            Err(errs) => errs,
+        };
+        let mut indices: Vec<usize> = errs.iter().map(|&(i, _)| i).collect();
+        indices.sort();
+        assert_eq!(indices, vec![1, 3]);
+    }
+
+    #[test]
+    fn build_validated_succeeds_when_every_pattern_is_well_formed() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add_regex("^lib\\.rs$");
+        let set = builder.build_validated().unwrap();
+
+        assert!(set.is_match("main.rs"));
+        assert!(set.is_match("lib.rs"));
+    }
+
+    #[test]
+    fn contains_reports_a_pattern_already_added_via_add_str() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add_str("*.rs").unwrap();
+
+        assert!(builder.contains("*.rs"));
+        assert!(!builder.contains("*.c"));
+
+        // A negated pattern with the same text doesn't count.
+        builder.add_negated(Glob::new("*.c").unwrap());
+        assert!(!builder.contains("*.c"));
+    }
+
+    #[test]
+    fn matches_candidate_iter_yields_every_matching_index() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let candidate = Candidate::new("src/lib.rs");
+        let mut hits: Vec<usize> =
+            set.matches_candidate_iter(&candidate).collect();
+        hits.sort();
+        assert_eq!(hits, vec![0, 1]);
+
+        let candidate = Candidate::new("foo.c");
+        assert_eq!(set.matches_candidate_iter(&candidate).count(), 0);
+    }
+
+    #[test]
+    fn add_str_and_extend_build_the_same_set_as_add() {
+        let mut via_add_str = GlobSetBuilder::new();
+        for pat in &["*.rs", "*.md", "*.toml", "src/*.c", "Cargo.lock"] {
+            via_add_str.add_str(pat).unwrap();
+        }
+        let via_add_str = via_add_str.build().unwrap();
+
+        let mut via_add = GlobSetBuilder::new();
+        via_add.extend(
+            ["*.rs", "*.md", "*.toml", "src/*.c", "Cargo.lock"]
+                .iter()
+                .map(|p| Glob::new(p).unwrap()),
+        );
+        let via_add = via_add.build().unwrap();
+
+        assert_eq!(via_add_str.len(), via_add.len());
+        assert_eq!(via_add_str.matches("src/foo.c"), via_add.matches("src/foo.c"));
+
+        let mut builder = GlobSetBuilder::new();
+        assert!(builder.add_str("src/{").is_err());
+    }
+
+    #[test]
+    fn from_bytes_matches_a_non_utf8_basename() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("dir/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        // A non-UTF-8 basename with no extension still matches via the
+        // wildcard, since the extension-based fast path never triggers on
+        // an extension-free candidate.
+        let mut path = b"dir/".to_vec();
+        path.extend_from_slice(b"\xffoo");
+        let candidate = Candidate::from_bytes(&path);
+        assert!(!set.is_match_candidate(&candidate));
+
+        let mut path = b"dir/".to_vec();
+        path.extend_from_slice(b"\xffoo.rs");
+        let candidate = Candidate::from_bytes(&path);
+        assert!(set.is_match_candidate(&candidate));
+    }
+
+    #[test]
+    fn new_normalized_agrees_with_new_on_normalized_input() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let path = "src/lib.rs";
+        let normal = Candidate::new(path);
+        let skip_normalize = Candidate::new_normalized(path);
+        assert_eq!(
+            set.matches_candidate(&normal),
+            set.matches_candidate(&skip_normalize));
+        assert!(set.is_match_candidate(&skip_normalize));
+    }
+
+    #[test]
+    fn candidate_exposes_path_basename_and_ext_accessors() {
+        let candidate = Candidate::new("src/foo.rs");
+        assert_eq!(candidate.path(), b"src/foo.rs");
+        assert_eq!(candidate.basename(), b"foo.rs");
+        assert_eq!(candidate.ext(), OsStr::new("rs"));
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn candidate_from_wide_matches_the_equivalent_utf8_path() {
+        let wide: Vec<u16> = "src/foo.rs".encode_utf16().collect();
+        let mut buf = ::std::ffi::OsString::new();
+        let candidate = Candidate::from_wide(&wide, &mut buf);
+        assert_eq!(candidate.path(), b"src/foo.rs");
+        assert_eq!(candidate.basename(), b"foo.rs");
+        assert_eq!(candidate.ext(), OsStr::new("rs"));
+    }
+
+    #[test]
+    fn candidate_buf_reset_reuses_storage_across_paths() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        let set = builder.build().unwrap();
+
+        let mut buf = CandidateBuf::new();
+        buf.reset("src/lib.rs");
+        assert!(set.is_match_candidate(&buf.as_candidate()));
+        assert_eq!(
+            set.matches_candidate(&buf.as_candidate()),
+            set.matches("src/lib.rs"));
+
+        // Resetting to a shorter path must not leave stale bytes behind
+        // from the longer one reused above.
+        buf.reset("a.md");
+        assert!(set.is_match_candidate(&buf.as_candidate()));
+        assert_eq!(
+            set.matches_candidate(&buf.as_candidate()),
+            set.matches("a.md"));
+
+        buf.reset("no_ext");
+        assert!(!set.is_match_candidate(&buf.as_candidate()));
+    }
+
+    #[test]
+    fn new_case_fold_precomputes_lowercased_path_basename_and_ext() {
+        let path = "Src/FOO/Bar.RS";
+        let plain = Candidate::new(path);
+        assert_eq!(plain.path_fold(), None);
+
+        let folded = Candidate::new_case_fold(path);
+        assert_eq!(folded.path_fold(), Some(&b"src/foo/bar.rs"[..]));
+        assert_eq!(folded.basename_fold(), Some(&b"bar.rs"[..]));
+        assert_eq!(folded.ext_fold(), Some(&b"rs"[..]));
+
+        // Folding never changes what the candidate actually matches.
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/**/*.rs").unwrap());
+        let set = builder.build().unwrap();
+        assert_eq!(set.matches_candidate(&folded), set.matches_candidate(&plain));
+    }
+
+    #[test]
+    fn case_insensitive_applies_to_the_whole_set() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        builder.case_insensitive(true);
+        let set = builder.build().unwrap();
+
+        assert!(set.is_match("FOO.RS"));
+        assert!(set.is_match("README.MD"));
+
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        let set = builder.build().unwrap();
+        assert!(!set.is_match("FOO.RS"));
+    }
+
+    #[test]
+    fn globs_exposes_the_original_patterns_in_insertion_order() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        builder.add(Glob::new("*.toml").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.globs().len(), 3);
+        assert_eq!(set.globs()[2].glob(), "*.toml");
+    }
+
+    #[test]
+    fn first_match_returns_the_smallest_matching_index() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.first_match("src/lib.rs"), Some(0));
+        assert_eq!(set.first_match("foo.c"), None);
+
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/*.rs").unwrap());
+        builder.add(Glob::new("*.rs").unwrap());
+        let set = builder.build().unwrap();
+        // "*.rs" (index 1) is the only pattern that matches a bare
+        // top-level file, even though "src/*.rs" (index 0) does not.
+        assert_eq!(set.first_match("lib.rs"), Some(1));
+    }
+
+    #[test]
+    fn could_match_under_prunes_unrelated_top_level_directories() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/**/*.rs").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert!(set.could_match_under(Path::new("src")));
+        assert!(set.could_match_under(Path::new("src/sub")));
+        assert!(!set.could_match_under(Path::new("tests")));
+        // A directory sharing a byte prefix, but not a path component,
+        // with "src" isn't confused for a real ancestor.
+        assert!(!set.could_match_under(Path::new("srcfoo")));
+    }
+
+    #[test]
+    fn could_match_under_is_conservative_without_a_literal_prefix() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("**/foo.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert!(set.could_match_under(Path::new("anything")));
+    }
+
+    #[test]
+    fn is_match_agrees_with_matches_is_empty() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/**/*.c").unwrap());
+        builder.add_regex("^lib\\.rs$");
+        builder.add(Glob::new("*.toml").unwrap());
+        let set = builder.build().unwrap();
+
+        for path in &["lib.rs", "src/foo/bar.c", "README.md", "Cargo.toml"] {
+            assert_eq!(
+                set.is_match(path),
+                !set.matches(path).is_empty(),
+                "path: {}", path);
+        }
+    }
+
+    #[test]
+    fn matches_priority_agrees_with_min_of_matches() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/*.rs").unwrap());
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        for path in &["src/lib.rs", "src/main.rs", "lib.rs", "foo.c"] {
+            let want = set.matches(path).into_iter().min();
+            assert_eq!(set.matches_priority(path), want, "path: {}", path);
+        }
+    }
+
+    #[test]
+    fn single_strategy_hits_skip_the_sort_but_stay_correct() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        builder.add(Glob::new("*.toml").unwrap());
+        let set = builder.build().unwrap();
+
+        // All three patterns route through the extension strategy alone,
+        // exercising the no-sort fast path.
+        assert_eq!(set.matches("foo.md"), vec![1]);
+    }
+
+    #[test]
+    fn duplicate_literal_patterns_hit_one_strategy_and_stay_sorted() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("foo.txt").unwrap());
+        builder.add(Glob::new("foo.txt").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.matches("foo.txt"), vec![0, 1]);
+    }
+
+    #[test]
+    fn matches_are_sorted_and_deduped_across_several_strategies() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/*").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        // Extension, prefix and literal strategies all fire for this path,
+        // in an order that doesn't naturally come out ascending.
+        assert_eq!(set.matches("src/lib.rs"), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn matches_capacity_agrees_with_matches_and_retains_capacity() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/*").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        let set = builder.build().unwrap();
+
+        let matches = set.matches_capacity("src/lib.rs", 8);
+        assert_eq!(matches, set.matches("src/lib.rs"));
+        assert!(matches.capacity() >= 8);
+    }
+
+    #[test]
+    fn count_matches_dedups_hits_from_several_strategies() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/*").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.count_matches("src/lib.rs"), 3);
+        assert_eq!(set.count_matches("foo.c"), 0);
+    }
+
+    #[test]
+    fn default_derives_on_structs_holding_a_glob_set() {
+        #[derive(Default)]
+        struct Config {
+            ignore: GlobSet,
+            builder: GlobSetBuilder,
+        }
+
+        let config = Config::default();
+        assert!(!config.ignore.is_match("anything"));
+        assert_eq!(config.builder.build().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn empty_constructor_matches_nothing() {
+        let set = GlobSet::empty();
+        assert_eq!(set.len(), 0);
+        assert!(set.is_empty());
+        assert!(!set.is_match("anything"));
+    }
+
+    #[test]
+    fn merge_reindexes_the_second_set_after_the_first() {
+        let mut includes = GlobSetBuilder::new();
+        includes.add(Glob::new("*.rs").unwrap());
+        includes.add(Glob::new("*.md").unwrap());
+        let includes = includes.build().unwrap();
+
+        let mut excludes = GlobSetBuilder::new();
+        excludes.add(Glob::new("target/*").unwrap());
+        let excludes = excludes.build().unwrap();
+
+        let merged = includes.merge(&excludes).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged.matches("foo.rs"), vec![0]);
+        assert_eq!(merged.matches("foo.md"), vec![1]);
+        assert_eq!(merged.matches("target/foo.o"), vec![2]);
+    }
+
+    #[test]
+    fn set_enabled_toggles_a_glob_out_of_every_match_query() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        let mut set = builder.build().unwrap();
+
+        assert!(set.is_enabled(0));
+        assert_eq!(set.matches("foo.rs"), vec![0]);
+        assert!(set.is_match("foo.rs"));
+        assert_eq!(set.first_match("foo.rs"), Some(0));
+
+        set.set_enabled(0, false);
+        assert!(!set.is_enabled(0));
+        assert!(set.matches("foo.rs").is_empty());
+        assert!(!set.is_match("foo.rs"));
+        assert_eq!(set.first_match("foo.rs"), None);
+        assert_eq!(
+            set.matches_candidate_iter(&Candidate::new("foo.rs"))
+                .collect::<Vec<usize>>(),
+            Vec::<usize>::new());
+
+        // The other glob is unaffected.
+        assert_eq!(set.matches("foo.md"), vec![1]);
+        assert!(set.is_match("foo.md"));
+
+        set.set_enabled(0, true);
+        assert_eq!(set.matches("foo.rs"), vec![0]);
+    }
+
+    #[test]
+    fn matches_subset_filters_by_predicate() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/*").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(
+            set.matches("src/lib.rs"),
+            vec![0, 1, 2]);
+
+        // Only consider indices in 1..3, as if index 0 had been disabled.
+        assert_eq!(
+            set.matches_subset("src/lib.rs", |i| i >= 1),
+            vec![1, 2]);
+        assert_eq!(
+            set.matches_subset("src/lib.rs", |i| i == 0),
+            vec![0]);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn par_matches_agrees_with_sequential_matches() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/*").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        let set = builder.build().unwrap();
+
+        let paths = vec![
+            "src/lib.rs", "README.md", "other/foo.c", "src/main.rs",
+        ];
+        let expected: Vec<Vec<usize>> =
+            paths.iter().map(|p| set.matches(p)).collect();
+        assert_eq!(set.par_matches(&paths), expected);
+    }
+
+    #[test]
+    #[cfg(feature = "smallvec")]
+    fn matches_smallvec_agrees_with_matches() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/*").unwrap());
+        let set = builder.build().unwrap();
+
+        for path in &["src/lib.rs", "README.md", "other/foo.c"] {
+            let expected = set.matches(path);
+            let got: Vec<usize> = set.matches_smallvec(path).into_iter().collect();
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn case_insensitive_extension_glob_matches_through_a_set() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.RS").unwrap());
+        let set = builder.build().unwrap();
+        // A case-sensitive `*.RS` doesn't take the fast extension-strategy
+        // path here since it wouldn't match "foo.rs" case-insensitively.
+        assert!(set.matches("foo.rs").is_empty());
+
+        builder.case_insensitive(true);
+        let set = builder.build().unwrap();
+        assert_eq!(set.matches("foo.rs"), vec![0]);
+    }
+
+    #[test]
+    fn matches_relative_strips_the_base_before_matching() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let base = Path::new("/home/me/proj");
+        assert_eq!(
+            set.matches_relative(base, "/home/me/proj/src/lib.rs"),
+            vec![0]);
+        assert!(
+            set.matches_relative(base, "/home/me/proj/README.md").is_empty());
+        assert!(
+            set.matches_relative(base, "/somewhere/else/src/lib.rs").is_empty());
+    }
+
+    #[test]
+    fn match_mask_agrees_with_the_sparse_matches_indices() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("*.md").unwrap());
+        builder.add(Glob::new("src/*").unwrap());
+        let set = builder.build().unwrap();
+
+        let mask = set.match_mask("src/lib.rs");
+        assert_eq!(mask, vec![true, false, true]);
+        assert_eq!(
+            set.matches("src/lib.rs"),
+            mask.iter()
+                .enumerate()
+                .filter(|&(_, &hit)| hit)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn is_match_str_and_matches_str_skip_renormalization() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert!(set.is_match_str("src/lib.rs"));
+        assert!(!set.is_match_str("README.md"));
+        assert_eq!(set.matches_str("src/lib.rs"), vec![0]);
+        assert!(set.matches_str("README.md").is_empty());
+    }
+
+    #[test]
+    fn matches_cwd_resolves_relative_and_absolute_paths_against_cwd() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let original = env::current_dir().unwrap();
+        env::set_current_dir(env!("CARGO_MANIFEST_DIR")).unwrap();
+
+        assert_eq!(set.matches_cwd("src/lib.rs"), vec![0]);
+        assert!(set.matches_cwd("README.md").is_empty());
+
+        let absolute =
+            env::current_dir().unwrap().join("src").join("lib.rs");
+        assert_eq!(set.matches_cwd(absolute), vec![0]);
+
+        assert!(set.matches_cwd("/somewhere/else/src/lib.rs").is_empty());
+
+        env::set_current_dir(original).unwrap();
+    }
+
+    #[test]
+    fn matches_stripping_drops_the_given_number_of_leading_components() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(
+            set.matches_stripping(2, "build/2024-01-01/src/lib.rs"),
+            vec![0]);
+        assert!(
+            set.matches_stripping(2, "build/2024-01-01/README.md").is_empty());
+    }
+
+    #[test]
+    fn matches_stripping_returns_empty_for_too_few_components() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*").unwrap());
+        let set = builder.build().unwrap();
+
+        assert!(set.matches_stripping(2, "one").is_empty());
+    }
+
+    #[test]
+    fn regex_set_prefilter_does_not_change_which_paths_match() {
+        // Complex `**` globs like these fall into the regex-set strategy,
+        // which is where the internal literal pre-filter kicks in. It must
+        // never change the answer, only how fast a non-match is rejected.
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("src/**/foo/**/*.rs").unwrap());
+        builder.add(Glob::new("lib/**/bar/**/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.matches("src/a/foo/b/main.rs"), vec![0]);
+        assert_eq!(set.matches("lib/a/bar/b/main.rs"), vec![1]);
+        assert!(set.matches("src/a/quux/b/main.rs").is_empty());
+        assert!(set.matches("other/foo/main.rs").is_empty());
+        assert!(set.matches("src/a/foo/b/main.c").is_empty());
+    }
+
+    #[test]
+    fn build_error_reports_the_index_of_the_failing_pattern() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add_regex("[invalid(");
+        let err = builder.build().unwrap_err();
+
+        assert_eq!(err.glob_index(), Some(1));
+    }
+
+    #[test]
+    fn explain_reports_the_extension_strategy_for_a_star_dot_ext_glob() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.explain("lib.rs"), vec![(0, MatchSource::Extension)]);
+        assert_eq!(set.explain("lib.c"), vec![]);
+    }
+
+    #[test]
+    fn explain_reports_the_literal_strategy_for_an_alternation_of_literals() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("{foo,bar,baz}.txt").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.explain("foo.txt"), vec![(0, MatchSource::Literal)]);
+        assert_eq!(set.explain("bar.txt"), vec![(0, MatchSource::Literal)]);
+        assert_eq!(set.explain("baz.txt"), vec![(0, MatchSource::Literal)]);
+        assert_eq!(set.explain("quux.txt"), vec![]);
+    }
+
+    #[test]
+    fn explain_reports_the_suffix_strategy_for_a_compound_extension_glob() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.tar.gz").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.explain("a.tar.gz"), vec![(0, MatchSource::Suffix)]);
+        assert_eq!(set.explain("archive/b.tar.gz"),
+            vec![(0, MatchSource::Suffix)]);
+        assert_eq!(set.explain("a.gz"), vec![]);
+        assert_eq!(set.explain("a.targz"), vec![]);
+    }
+
+    #[test]
+    fn strategies_reports_the_strategy_each_pattern_compiled_to() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/**/foo/**/*.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        let got: Vec<(usize, MatchSource)> = set.strategies().collect();
+        assert_eq!(
+            got,
+            vec![(0, MatchSource::Extension), (1, MatchSource::Regex)]);
+    }
+
+    #[test]
+    fn glob_set_builder_regex_size_limit_can_be_raised() {
+        let branches: Vec<String> =
+            (0..200).map(|i| format!("branch{}", i)).collect();
+        let pat = format!("{{{}}}", branches.join(","));
+
+        let mut too_small = GlobSetBuilder::new();
+        too_small.add_regex(&pat);
+        let err = too_small.regex_size_limit(16).build().unwrap_err();
+        match *err.kind() {
+            ErrorKind::Regex(_) => {}
+            ref kind => panic!("unexpected error kind: {:?}", kind),
+        }
+
+        let mut raised = GlobSetBuilder::new();
+        raised.add_regex(&pat);
+        let set = raised.regex_size_limit(1 << 20).build().unwrap();
+        assert!(set.is_match("branch5"));
+    }
+
+    #[test]
+    fn most_specific_match_prefers_the_longer_literal_prefix() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("src/lib.rs").unwrap());
+        let set = builder.build().unwrap();
+
+        assert_eq!(set.most_specific_match("src/lib.rs"), Some(1));
+        assert_eq!(set.most_specific_match("src/other.rs"), Some(0));
+        assert_eq!(set.most_specific_match("src/other.txt"), None);
+    }
+
+    #[test]
+    fn stats_counts_each_pattern_under_its_compiled_strategy() {
+        let mut builder = GlobSetBuilder::new();
+        builder.add(Glob::new("*.rs").unwrap());
+        builder.add(Glob::new("Makefile").unwrap());
+        builder.add(Glob::new("src/**/foo/**/*.rs").unwrap());
+        builder.add_regex("foo(bar)?");
+        let set = builder.build().unwrap();
+
+        let stats = set.stats();
+        assert_eq!(stats.extensions, 1);
+        assert_eq!(stats.literals, 1);
+        assert_eq!(stats.regexes, 2);
+        assert_eq!(stats.basename_literals, 0);
+        assert_eq!(stats.prefixes, 0);
+        assert_eq!(stats.suffixes, 0);
+        assert_eq!(stats.required_extensions, 0);
+        assert!(stats.approx_bytes > 0);
     }
 }
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use glob::GlobMatcher;
+
+/// A node in the byte trie backing `PathSet`.
+///
+/// `indices` holds the sequence numbers of every path that ends exactly at
+/// this node; more than one is possible only if the same path string was
+/// inserted twice.
+#[derive(Clone, Debug, Default)]
+struct TrieNode {
+    children: HashMap<u8, TrieNode>,
+    indices: Vec<usize>,
+}
+
+impl TrieNode {
+    fn collect_indices(&self, out: &mut Vec<usize>) {
+        out.extend_from_slice(&self.indices);
+        for child in self.children.values() {
+            child.collect_indices(out);
+        }
+    }
+}
+
+/// A set of concrete, literal paths, indexed for answering "which of my
+/// paths does this glob select" — the dual of `GlobSet`, which answers
+/// "which of my globs does this path match".
+///
+/// Paths are stored in a trie keyed by byte, which lets `matches_glob`
+/// prune straight to the paths sharing a glob's `Glob::literal_prefix`
+/// instead of testing every stored path individually.
+#[derive(Clone, Debug, Default)]
+pub struct PathSet {
+    root: TrieNode,
+    paths: Vec<String>,
+}
+
+impl PathSet {
+    /// Creates a new, empty `PathSet`.
+    pub fn new() -> PathSet {
+        PathSet { root: TrieNode::default(), paths: vec![] }
+    }
+
+    /// Returns the number of paths in this set.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns true if and only if this set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Adds `path` to this set, returning the sequence number it can later
+    /// be identified by in `matches_glob`'s result.
+    ///
+    /// Inserting the same path more than once is allowed; each insertion
+    /// gets its own sequence number and can be reported independently.
+    pub fn insert(&mut self, path: &str) -> usize {
+        let index = self.paths.len();
+        self.paths.push(path.to_string());
+
+        let mut node = &mut self.root;
+        for &b in path.as_bytes() {
+            node = node.children.entry(b).or_insert_with(TrieNode::default);
+        }
+        node.indices.push(index);
+        index
+    }
+
+    /// Returns the path at `index`, as given to `insert`.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.paths.get(index).map(|s| s.as_str())
+    }
+
+    /// Returns the sequence numbers of every stored path that `matcher`
+    /// matches, in ascending order.
+    ///
+    /// This first descends the trie by `matcher.glob().literal_prefix()`,
+    /// so a pattern like `src/*.rs` only tests paths actually starting
+    /// with `src/` rather than every path in the set, then falls back to
+    /// `GlobMatcher::is_match` to confirm each candidate, since the trie
+    /// alone can't evaluate wildcards, character classes, or alternation.
+    pub fn matches_glob(&self, matcher: &GlobMatcher) -> Vec<usize> {
+        let prefix = matcher.glob().literal_prefix();
+        let mut node = &self.root;
+        for &b in prefix.as_bytes() {
+            match node.children.get(&b) {
+                Some(child) => node = child,
+                None => return vec![],
+            }
+        }
+
+        let mut candidates = vec![];
+        node.collect_indices(&mut candidates);
+        candidates.sort();
+
+        candidates
+            .into_iter()
+            .filter(|&i| matcher.is_match(&self.paths[i]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glob::Glob;
+    use super::PathSet;
+
+    #[test]
+    fn matches_glob_finds_stored_paths_selected_by_a_pattern() {
+        let mut set = PathSet::new();
+        let rs = set.insert("src/lib.rs");
+        let main = set.insert("src/main.rs");
+        set.insert("README.md");
+        set.insert("src/data.txt");
+
+        let matcher = Glob::new("src/*.rs").unwrap().compile_matcher();
+        let mut got = set.matches_glob(&matcher);
+        got.sort();
+        let mut want = vec![rs, main];
+        want.sort();
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn matches_glob_returns_empty_when_no_path_shares_the_literal_prefix() {
+        let mut set = PathSet::new();
+        set.insert("src/lib.rs");
+
+        let matcher = Glob::new("tests/*.rs").unwrap().compile_matcher();
+        assert!(set.matches_glob(&matcher).is_empty());
+    }
+}
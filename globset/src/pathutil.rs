@@ -49,8 +49,63 @@ pub fn file_name_ext<'a>(name: &'a OsStr) -> Option<&'a OsStr> {
     Some(OsStr::new(str::from_utf8(&name[last_dot_at+1..]).unwrap()))
 }
 
+/// The file stem of this file name: everything before the extension that
+/// `file_name_ext` would report, or the whole name if it has none.
+pub fn file_name_stem<'a>(name: &'a OsStr) -> &'a OsStr {
+    let bytes = match os_str_bytes(name) {
+        Cow::Owned(_) => return name,
+        Cow::Borrowed(name) => name,
+    };
+    match bytes.iter().enumerate().rev().find(|&(_, &b)| b == b'.') {
+        None => name,
+        Some((i, _)) => OsStr::new(str::from_utf8(&bytes[..i]).unwrap()),
+    }
+}
+
+/// The final path component of `path`, found by scanning for the last `/`
+/// directly, rather than going through `Path`. Returns all of `path` if it
+/// contains no `/`.
+pub fn file_name_bytes(path: &[u8]) -> &[u8] {
+    match path.iter().rposition(|&b| b == b'/') {
+        Some(i) => &path[i + 1..],
+        None => path,
+    }
+}
+
+/// The extension of a file name found via `file_name_bytes`, if one exists,
+/// found by scanning for the last `.` directly.
+pub fn file_name_ext_bytes(name: &[u8]) -> Option<&[u8]> {
+    match name.iter().rposition(|&b| b == b'.') {
+        Some(i) => Some(&name[i + 1..]),
+        None => None,
+    }
+}
+
+/// The file stem of a file name found via `file_name_bytes`: everything
+/// before the extension `file_name_ext_bytes` would report, or the whole
+/// name if it has none.
+pub fn file_stem_bytes(name: &[u8]) -> &[u8] {
+    match name.iter().rposition(|&b| b == b'.') {
+        Some(i) => &name[..i],
+        None => name,
+    }
+}
+
 /// Normalizes a path to use `/` as a separator everywhere, even on
 /// platforms that recognize `\` as a separator in addition to `/`.
+/// Normalizes a path's separators to `/`, and, on Windows, strips the
+/// `\\?\` verbatim prefix that `std::fs::canonicalize` and friends like to
+/// produce, so that a candidate path like `\\?\C:\foo\bar.txt` still
+/// matches an ordinary glob like `C:/foo/*.txt`. The two verbatim forms
+/// this handles are:
+///
+/// * `\\?\C:\foo\bar` (verbatim disk), which becomes `C:/foo/bar`.
+/// * `\\?\UNC\server\share\foo` (verbatim UNC), which becomes
+///   `//server/share/foo`, matching the non-verbatim UNC form.
+///
+/// Ordinary (non-verbatim) UNC paths, e.g. `\\server\share\foo`, are left
+/// as `//server/share/foo` after separator normalization; no prefix is
+/// stripped since there isn't one to strip.
 pub fn normalize_path(mut path: Cow<[u8]>) -> Cow<[u8]> {
     if cfg!(windows) && path.iter().position(|&b| b == b'\\').is_some() {
         for i in 0..path.len() {
@@ -58,8 +113,125 @@ pub fn normalize_path(mut path: Cow<[u8]>) -> Cow<[u8]> {
                 path.to_mut()[i] = b'/';
             }
         }
+    }
+    if cfg!(windows) {
+        if let Some(rest) = strip_bytes_prefix(&path, b"//?/UNC/") {
+            let mut unc = b"//".to_vec();
+            unc.extend_from_slice(rest);
+            return collapse_dots_and_slashes(Cow::Owned(unc));
+        }
+        if let Some(rest) = strip_bytes_prefix(&path, b"//?/") {
+            return collapse_dots_and_slashes(Cow::Owned(rest.to_vec()));
+        }
+    }
+    collapse_dots_and_slashes(path)
+}
+
+/// Collapses `.` path components and runs of repeated `/` out of `path`,
+/// so `src//foo/./bar.rs` reads the same as `src/foo/bar.rs`.
+///
+/// A run of exactly two leading slashes is left alone, since POSIX gives
+/// that form an implementation-defined meaning distinct from a single
+/// leading slash; any other run (one, or three or more) collapses to one.
+/// This is applied to both candidate paths (via `normalize_path`) and, in
+/// `GlobBuilder::build`, to glob pattern text itself, so the two sides
+/// agree on what counts as the "same" path.
+pub(crate) fn collapse_dots_and_slashes(path: Cow<[u8]>) -> Cow<[u8]> {
+    if !path.contains(&b'/') {
+        return path;
+    }
+    let leading_slashes = path.iter().take_while(|&&b| b == b'/').count();
+    // A trailing `/` can be meaningful (e.g. a gitignore-style directory
+    // marker on a glob pattern), so a single one is preserved even though
+    // it looks like just another empty component to the loop below.
+    let trailing_slash =
+        path.len() > leading_slashes && path[path.len() - 1] == b'/';
+    let mut out = Vec::with_capacity(path.len());
+    if leading_slashes == 2 {
+        out.extend_from_slice(b"//");
+    } else if leading_slashes > 0 {
+        out.push(b'/');
+    }
+    for component in path[leading_slashes..].split(|&b| b == b'/') {
+        if component.is_empty() || component == b"." {
+            continue;
+        }
+        if !out.is_empty() && out.last() != Some(&b'/') {
+            out.push(b'/');
+        }
+        out.extend_from_slice(component);
+    }
+    if trailing_slash && out.last() != Some(&b'/') {
+        out.push(b'/');
+    }
+    if out.is_empty() {
+        out.push(b'.');
+    }
+    if &out[..] == &*path {
         path
     } else {
-        path
+        Cow::Owned(out)
+    }
+}
+
+/// Returns `haystack` with `prefix` removed from its front, if present.
+fn strip_bytes_prefix<'a>(
+    haystack: &'a [u8],
+    prefix: &[u8],
+) -> Option<&'a [u8]> {
+    if haystack.len() >= prefix.len() && &haystack[..prefix.len()] == prefix {
+        Some(&haystack[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{collapse_dots_and_slashes, normalize_path};
+
+    #[test]
+    #[cfg(windows)]
+    fn normalize_path_strips_verbatim_disk_prefix() {
+        let got = normalize_path(Cow::Borrowed(br"\\?\C:\foo\bar.txt"));
+        assert_eq!(&*got, &b"C:/foo/bar.txt"[..]);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn normalize_path_rewrites_verbatim_unc_prefix() {
+        let got = normalize_path(Cow::Borrowed(br"\\?\UNC\server\share\foo"));
+        assert_eq!(&*got, &b"//server/share/foo"[..]);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn normalize_path_leaves_ordinary_unc_paths_alone() {
+        let got = normalize_path(Cow::Borrowed(br"\\server\share\foo"));
+        assert_eq!(&*got, &b"//server/share/foo"[..]);
+    }
+
+    #[test]
+    fn collapse_drops_dot_components_and_repeated_slashes() {
+        let got = collapse_dots_and_slashes(
+            Cow::Borrowed(&b"src//foo/./bar.rs"[..]));
+        assert_eq!(&*got, &b"src/foo/bar.rs"[..]);
+    }
+
+    #[test]
+    fn collapse_preserves_exactly_two_leading_slashes() {
+        let got = collapse_dots_and_slashes(Cow::Borrowed(&b"//foo/bar"[..]));
+        assert_eq!(&*got, &b"//foo/bar"[..]);
+
+        let got = collapse_dots_and_slashes(Cow::Borrowed(&b"///foo/bar"[..]));
+        assert_eq!(&*got, &b"/foo/bar"[..]);
+    }
+
+    #[test]
+    fn collapse_preserves_a_meaningful_trailing_slash() {
+        let got = collapse_dots_and_slashes(Cow::Borrowed(&b"build//"[..]));
+        assert_eq!(&*got, &b"build/"[..]);
     }
 }